@@ -0,0 +1,396 @@
+// The audio processing unit, which is part of the CPU chip. Its channels' timers run off the CPU
+// clock, and its frame counter clocks their envelopes, sweeps and length counters a few times per
+// (roughly 60Hz) frame.
+
+mod dmc;
+mod filter;
+mod frame_counter;
+mod noise;
+mod pulse;
+mod resampler;
+mod triangle;
+
+use crate::apu::dmc::Dmc;
+use crate::apu::filter::FilterChain;
+use crate::apu::frame_counter::{FrameClock, FrameCounter};
+use crate::apu::noise::Noise;
+use crate::apu::pulse::Pulse;
+use crate::apu::resampler::Resampler;
+use crate::apu::triangle::Triangle;
+use crate::rom::Region;
+use crate::savestate::{StateReader, StateWriter};
+
+// Length counter values, indexed by the top 5 bits written to a channel's length register
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+// The mixer's output for the pulse channels' combined level (0-30), and for the triangle, noise
+// and DMC channels' weighted level (3 * triangle + 2 * noise + DMC, 0-202). Each group goes
+// through its own non-linear DAC, so a channel gets quieter the more the others in its group are
+// playing.
+const PULSE_TABLE: [f32; 31] = mixer_table(95.52, 8128.0);
+const TND_TABLE: [f32; 203] = mixer_table(163.67, 24329.0);
+
+const fn mixer_table<const N: usize>(scale: f32, divisor: f32) -> [f32; N] {
+    let mut table = [0.0; N];
+    let mut i = 1;
+    while i < N {
+        table[i] = scale / (divisor / i as f32 + 100.0);
+        i += 1;
+    }
+    table
+}
+
+// Look up a level in a mixer table, interpolating between entries for levels scaled by a channel
+// volume.
+fn mix(table: &[f32], level: f32) -> f32 {
+    let index = level as usize;
+    if index + 1 >= table.len() {
+        return table[table.len() - 1];
+    }
+
+    let frac = level - index as f32;
+    table[index] + (table[index + 1] - table[index]) * frac
+}
+
+// The APU's channels, for adjusting how they're mixed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+}
+
+// Silences a channel after it's played for a set number of half frames, unless it's halted.
+#[derive(Default)]
+pub struct LengthCounter {
+    enabled: bool, // through $4015; a disabled channel's counter stays at 0
+    pub halted: bool,
+    counter: u8,
+}
+
+impl LengthCounter {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    pub fn load(&mut self, index: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[usize::from(index & 0x1F)];
+        }
+    }
+
+    // Clocked every half frame
+    pub fn clock(&mut self) {
+        if !self.halted && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.counter > 0
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_bool(self.halted);
+        w.write_u8(self.counter);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.enabled = r.read_bool()?;
+        self.halted = r.read_bool()?;
+        self.counter = r.read_u8()?;
+        Ok(())
+    }
+}
+
+// Volume of the pulse and noise channels: either constant, or a sawtooth decaying from 15 to 0,
+// optionally looping.
+#[derive(Default)]
+pub struct Envelope {
+    start: bool, // set by writes to the channel's length register, to restart the decay
+    looping: bool,
+    constant_volume: bool,
+    volume: u8, // the constant volume, or the decay's divider period
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    // Bits 0-5 of the channel's first register
+    pub fn write_control(&mut self, val: u8) {
+        self.looping = val & 0x20 != 0;
+        self.constant_volume = val & 0x10 != 0;
+        self.volume = val & 0x0F;
+    }
+
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    // Clocked every quarter frame
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.start);
+        w.write_bool(self.looping);
+        w.write_bool(self.constant_volume);
+        w.write_u8(self.volume);
+        w.write_u8(self.divider);
+        w.write_u8(self.decay);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.start = r.read_bool()?;
+        self.looping = r.read_bool()?;
+        self.constant_volume = r.read_bool()?;
+        self.volume = r.read_u8()?;
+        self.divider = r.read_u8()?;
+        self.decay = r.read_u8()?;
+        Ok(())
+    }
+}
+
+pub struct Apu {
+    region: Region,
+    pub pulse_1: Pulse,
+    pub pulse_2: Pulse,
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc: Dmc,
+    pub frame_counter: FrameCounter,
+    odd_cycle: bool, // the pulse channels' timers are clocked every other CPU cycle
+
+    // How loud each channel is mixed, by Channel, from 0.0 to 1.0. These are settings for whoever
+    // is listening rather than part of the console, so they're kept across power cycles and left
+    // out of savestates.
+    volumes: [f32; 5],
+    muted: [bool; 5],
+
+    resampler: Resampler,
+    filters: FilterChain,
+    pub samples: Vec<f32>, // produced since the last time they were taken
+}
+
+impl Apu {
+    pub fn new(region: Region, sample_rate: u32) -> Self {
+        let cpu_hz = region.master_clock_hz() as f64 / region.cpu_divider() as f64;
+
+        Self {
+            region,
+            pulse_1: Pulse::new(true),
+            pulse_2: Pulse::new(false),
+            triangle: Triangle::new(),
+            noise: Noise::new(region),
+            dmc: Dmc::new(region),
+            frame_counter: FrameCounter::new(region),
+            odd_cycle: false,
+            volumes: [1.0; 5],
+            muted: [false; 5],
+            resampler: Resampler::new(cpu_hz, sample_rate),
+            filters: FilterChain::new(sample_rate),
+            samples: Vec::new(),
+        }
+    }
+
+    // Rate, in Hz, samples are produced at.
+    pub fn sample_rate(&self) -> u32 {
+        self.resampler.sample_rate()
+    }
+
+    // Produce samples a little faster (above 1.0) or slower than the sample rate. This is how the
+    // frontend keeps its audio buffer from running dry or filling up, since the console's clock
+    // and the audio device's don't quite agree.
+    pub fn set_rate_adjustment(&mut self, rate_adjustment: f64) {
+        self.resampler.set_rate_adjustment(rate_adjustment);
+    }
+
+    pub fn power_on(&mut self) {
+        let (volumes, muted) = (self.volumes, self.muted);
+        *self = Self::new(self.region, self.sample_rate());
+        self.volumes = volumes;
+        self.muted = muted;
+    }
+
+    // Scale a channel's level before it's mixed, from 0.0 (silent) to 1.0 (as the console plays
+    // it). NaN and infinities, which clamp would let through or pin to full volume, silence it.
+    pub fn set_channel_volume(&mut self, channel: Channel, volume: f32) {
+        self.volumes[channel as usize] = if volume.is_finite() {
+            volume.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+    }
+
+    pub fn channel_volume(&self, channel: Channel) -> f32 {
+        self.volumes[channel as usize]
+    }
+
+    // Silence a channel without losing its volume setting.
+    pub fn set_channel_muted(&mut self, channel: Channel, muted: bool) {
+        self.muted[channel as usize] = muted;
+    }
+
+    pub fn channel_muted(&self, channel: Channel) -> bool {
+        self.muted[channel as usize]
+    }
+
+    // Resetting the console silences every channel, as if $4015 were written with 0, and restarts
+    // the frame counter.
+    pub fn reset(&mut self) {
+        self.write_register(0x4015, 0);
+        self.frame_counter.reset(self.odd_cycle);
+    }
+
+    // $4015: which channels' length counters are still running (or, for the DMC, whether it has
+    // sample bytes left to play), and the interrupt flags. Reading it acknowledges the frame IRQ.
+    pub fn read_status(&mut self) -> u8 {
+        let status = u8::from(self.pulse_1.length.active())
+            | u8::from(self.pulse_2.length.active()) << 1
+            | u8::from(self.triangle.length.active()) << 2
+            | u8::from(self.noise.length.active()) << 3
+            | u8::from(self.dmc.bytes_remaining > 0) << 4
+            | u8::from(self.frame_counter.irq) << 6
+            | u8::from(self.dmc.irq) << 7;
+
+        self.frame_counter.irq = false;
+
+        status
+    }
+
+    // Writes to $4000-$4013, $4015 and $4017.
+    pub fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse_1.write_register(addr & 0x3, val),
+            0x4004..=0x4007 => self.pulse_2.write_register(addr & 0x3, val),
+            0x4008..=0x400B => self.triangle.write_register(addr & 0x3, val),
+            0x400C..=0x400F => self.noise.write_register(addr & 0x3, val),
+            0x4010..=0x4013 => self.dmc.write_register(addr & 0x3, val),
+            // Channel enables
+            0x4015 => {
+                self.pulse_1.length.set_enabled(val & 0x01 != 0);
+                self.pulse_2.length.set_enabled(val & 0x02 != 0);
+                self.triangle.length.set_enabled(val & 0x04 != 0);
+                self.noise.length.set_enabled(val & 0x08 != 0);
+                self.dmc.set_enabled(val & 0x10 != 0);
+            }
+            0x4017 => self.frame_counter.write(val, self.odd_cycle),
+            _ => {}
+        }
+    }
+
+    // Run the APU for one CPU cycle.
+    pub fn tick(&mut self) {
+        if self.odd_cycle {
+            self.pulse_1.clock_timer();
+            self.pulse_2.clock_timer();
+        }
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        self.odd_cycle = !self.odd_cycle;
+
+        match self.frame_counter.tick() {
+            FrameClock::None => {}
+            FrameClock::Quarter => self.clock_quarter_frame(),
+            FrameClock::Half => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+        }
+
+        let output = self.output();
+        let new_samples = self.samples.len();
+        self.resampler.clock(output, &mut self.samples);
+        for sample in &mut self.samples[new_samples..] {
+            *sample = self.filters.process(*sample);
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse_1.envelope.clock();
+        self.pulse_2.envelope.clock();
+        self.triangle.clock_linear_counter();
+        self.noise.envelope.clock();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse_1.length.clock();
+        self.pulse_1.clock_sweep();
+        self.pulse_2.length.clock();
+        self.pulse_2.clock_sweep();
+        self.triangle.length.clock();
+        self.noise.length.clock();
+    }
+
+    // The channels mixed together, from 0.0 to about 1.0.
+    fn output(&self) -> f32 {
+        let level = |channel: Channel, output: u8| {
+            if self.muted[channel as usize] {
+                0.0
+            } else {
+                f32::from(output) * self.volumes[channel as usize]
+            }
+        };
+
+        let pulse = level(Channel::Pulse1, self.pulse_1.output())
+            + level(Channel::Pulse2, self.pulse_2.output());
+        let tnd = 3.0 * level(Channel::Triangle, self.triangle.output())
+            + 2.0 * level(Channel::Noise, self.noise.output())
+            + level(Channel::Dmc, self.dmc.output());
+
+        mix(&PULSE_TABLE, pulse) + mix(&TND_TABLE, tnd)
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        self.pulse_1.save_state(w);
+        self.pulse_2.save_state(w);
+        self.triangle.save_state(w);
+        self.noise.save_state(w);
+        self.dmc.save_state(w);
+        self.frame_counter.save_state(w);
+        w.write_bool(self.odd_cycle);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.pulse_1.load_state(r)?;
+        self.pulse_2.load_state(r)?;
+        self.triangle.load_state(r)?;
+        self.noise.load_state(r)?;
+        self.dmc.load_state(r)?;
+        self.frame_counter.load_state(r)?;
+        self.odd_cycle = r.read_bool()?;
+        Ok(())
+    }
+}
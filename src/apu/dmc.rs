@@ -0,0 +1,189 @@
+use crate::rom::Region;
+use crate::savestate::{StateReader, StateWriter};
+
+// Timer periods in CPU cycles, indexed by the low 4 bits of $4010
+const NTSC_PERIODS: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const PAL_PERIODS: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+// The delta modulation channel ($4010-$4013): plays 1-bit delta-encoded samples from $C000-$FFFF,
+// each bit moving a 7-bit output level up or down by 2. Sample bytes are fetched one at a time
+// by halting the CPU and reading through its bus (see Cpu::dma), and the channel can raise an IRQ
+// when a sample ends.
+pub struct Dmc {
+    periods: &'static [u16; 16],
+    irq_enabled: bool,
+    pub irq: bool, // asserting the IRQ line; cleared by writes to $4010 and $4015
+    looping: bool,
+    timer_period: u16,
+    timer: u16,
+    output_level: u8,
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    pub bytes_remaining: u16,
+    sample_buffer: Option<u8>, // the next byte to play, once the shift register is done
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool, // the sample buffer was empty when the shift register needed refilling
+}
+
+impl Dmc {
+    pub fn new(region: Region) -> Self {
+        let periods = match region {
+            Region::Ntsc => &NTSC_PERIODS,
+            Region::Pal => &PAL_PERIODS,
+        };
+
+        Self {
+            periods,
+            irq_enabled: false,
+            irq: false,
+            looping: false,
+            timer_period: periods[0] - 1,
+            timer: 0,
+            output_level: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+        }
+    }
+
+    pub fn write_register(&mut self, reg: u16, val: u8) {
+        match reg {
+            0 => {
+                self.irq_enabled = val & 0x80 != 0;
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+                self.looping = val & 0x40 != 0;
+                self.timer_period = self.periods[usize::from(val & 0x0F)] - 1;
+            }
+            1 => self.output_level = val & 0x7F,
+            2 => self.sample_address = 0xC000 | (u16::from(val) << 6),
+            _ => self.sample_length = (u16::from(val) << 4) + 1,
+        }
+    }
+
+    // Bit 4 of $4015 starts the sample if it isn't playing, or stops it.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    // Where the next sample byte should be fetched from, if the sample buffer needs filling.
+    pub fn dma_address(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    // Take the byte fetched from dma_address().
+    pub fn fill_sample_buffer(&mut self, val: u8) {
+        self.sample_buffer = Some(val);
+        self.current_address = self.current_address.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    // Clocked every CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period;
+
+        // Change the output level by the next bit, unless that would take it out of range
+        if !self.silence {
+            if self.shift_register & 0x1 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(val) => {
+                    self.shift_register = val;
+                    self.silence = false;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.irq);
+        w.write_bool(self.looping);
+        w.write_u16(self.timer_period);
+        w.write_u16(self.timer);
+        w.write_u8(self.output_level);
+        w.write_u16(self.sample_address);
+        w.write_u16(self.sample_length);
+        w.write_u16(self.current_address);
+        w.write_u16(self.bytes_remaining);
+        w.write_bool(self.sample_buffer.is_some());
+        w.write_u8(self.sample_buffer.unwrap_or(0));
+        w.write_u8(self.shift_register);
+        w.write_u8(self.bits_remaining);
+        w.write_bool(self.silence);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.irq_enabled = r.read_bool()?;
+        self.irq = r.read_bool()?;
+        self.looping = r.read_bool()?;
+        self.timer_period = r.read_u16()?;
+        self.timer = r.read_u16()?;
+        self.output_level = r.read_u8()?;
+        self.sample_address = r.read_u16()?;
+        self.sample_length = r.read_u16()?;
+        self.current_address = r.read_u16()?;
+        self.bytes_remaining = r.read_u16()?;
+        let buffer_full = r.read_bool()?;
+        let buffer = r.read_u8()?;
+        self.sample_buffer = buffer_full.then_some(buffer);
+        self.shift_register = r.read_u8()?;
+        self.bits_remaining = r.read_u8()?;
+        self.silence = r.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FilterKind {
+    HighPass,
+    LowPass,
+}
+
+// A first-order (RC) filter, run at the output sample rate.
+struct Filter {
+    kind: FilterKind,
+    alpha: f32,
+    prev_input: f32,
+    prev_output: f32,
+}
+
+impl Filter {
+    fn new(kind: FilterKind, cutoff_hz: f32, sample_rate: u32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff_hz);
+        let dt = 1.0 / sample_rate as f32;
+
+        Self {
+            kind,
+            alpha: match kind {
+                FilterKind::HighPass => rc / (rc + dt),
+                FilterKind::LowPass => dt / (rc + dt),
+            },
+            prev_input: 0.0,
+            prev_output: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = match self.kind {
+            FilterKind::HighPass => self.alpha * (self.prev_output + input - self.prev_input),
+            FilterKind::LowPass => self.prev_output + self.alpha * (input - self.prev_output),
+        };
+
+        self.prev_input = input;
+        self.prev_output = output;
+        output
+    }
+}
+
+// The filters between the APU and the console's audio output: two high-pass filters, at 90Hz and
+// 440Hz, which take out the DC offset and thin out the bass, and a low-pass at 14kHz.
+pub struct FilterChain {
+    filters: [Filter; 3],
+}
+
+impl FilterChain {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            filters: [
+                Filter::new(FilterKind::HighPass, 90.0, sample_rate),
+                Filter::new(FilterKind::HighPass, 440.0, sample_rate),
+                Filter::new(FilterKind::LowPass, 14_000.0, sample_rate),
+            ],
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.filters
+            .iter_mut()
+            .fold(sample, |sample, filter| filter.process(sample))
+    }
+}
@@ -0,0 +1,141 @@
+use crate::rom::Region;
+use crate::savestate::{StateReader, StateWriter};
+
+// What the frame counter clocks on a given CPU cycle. A half frame clock comes with a quarter
+// frame clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameClock {
+    None,
+    Quarter, // envelopes and the triangle's linear counter
+    Half,    // also length counters and sweeps
+}
+
+// Divides the CPU clock down to the steps of the APU's frame sequence, each of which clocks the
+// channels' envelopes, and every other one their length counters and sweeps. It's set up through
+// $4017: the 4-step sequence raises the frame IRQ at the end of each pass unless that's
+// inhibited, and the 5-step one adds a step that clocks nothing, and never raises it.
+pub struct FrameCounter {
+    steps: [u32; 5], // CPU cycles into the sequence that each step happens
+    cycle: u32,
+    five_step: bool,
+    irq_inhibit: bool,
+    pub irq: bool, // asserting the IRQ line; cleared by reading $4015
+    // A $4017 write that hasn't taken effect yet, and the cycles until it does
+    pending_write: Option<(u8, u8)>,
+}
+
+impl FrameCounter {
+    pub fn new(region: Region) -> Self {
+        let mut res = Self {
+            steps: match region {
+                Region::Ntsc => [7457, 14913, 22371, 29829, 37281],
+                Region::Pal => [8313, 16627, 24939, 33253, 41565],
+            },
+            cycle: 0,
+            five_step: false,
+            irq_inhibit: false,
+            irq: false,
+            pending_write: None,
+        };
+
+        res.reset(false);
+
+        res
+    }
+
+    // After power on or a reset the console acts as if $4017 had been written with the mode it
+    // was last in (4-step at power on) a few cycles before the first instruction, so how far the
+    // sequence gets before the game starts jitters by a cycle depending on the APU's cycle.
+    pub fn reset(&mut self, odd_cycle: bool) {
+        self.irq = false;
+        self.write(if self.five_step { 0x80 } else { 0x00 }, odd_cycle);
+    }
+
+    // $4017. The IRQ inhibit flag applies straight away, but the sequence restarts (in the new
+    // mode) 3 CPU cycles after the write if it's made on an APU cycle, or 4 if it's made between
+    // them.
+    pub fn write(&mut self, val: u8, odd_cycle: bool) {
+        self.irq_inhibit = val & 0x40 != 0;
+        if self.irq_inhibit {
+            self.irq = false;
+        }
+
+        // Counting the cycle the write is made on
+        let delay = if odd_cycle { 4 } else { 5 };
+        self.pending_write = Some((val, delay));
+    }
+
+    // Run the frame counter for one CPU cycle.
+    pub fn tick(&mut self) -> FrameClock {
+        self.cycle += 1;
+
+        let last_step = if self.five_step {
+            self.steps[4]
+        } else {
+            self.steps[3]
+        };
+
+        let mut clock = if self.cycle == self.steps[0] || self.cycle == self.steps[2] {
+            FrameClock::Quarter
+        } else if self.cycle == self.steps[1] || self.cycle == last_step {
+            FrameClock::Half
+        } else {
+            FrameClock::None
+        };
+
+        // The IRQ flag is set for three cycles around the last step, so clearing it in the
+        // first two doesn't stick
+        if !self.five_step
+            && !self.irq_inhibit
+            && self.cycle + 1 >= last_step
+            && self.cycle <= last_step + 1
+        {
+            self.irq = true;
+        }
+
+        // The sequence repeats one cycle after its last step
+        if self.cycle > last_step {
+            self.cycle = 0;
+        }
+
+        if let Some((val, delay)) = self.pending_write {
+            if delay > 1 {
+                self.pending_write = Some((val, delay - 1));
+            } else {
+                self.pending_write = None;
+                self.five_step = val & 0x80 != 0;
+                self.cycle = 0;
+
+                // Starting the 5-step sequence clocks everything immediately
+                if self.five_step {
+                    clock = FrameClock::Half;
+                }
+            }
+        }
+
+        clock
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u32(self.cycle);
+        w.write_bool(self.five_step);
+        w.write_bool(self.irq_inhibit);
+        w.write_bool(self.irq);
+        w.write_bool(self.pending_write.is_some());
+        let (val, delay) = self.pending_write.unwrap_or((0, 0));
+        w.write_u8(val);
+        w.write_u8(delay);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.cycle = r.read_u32()?;
+        self.five_step = r.read_bool()?;
+        self.irq_inhibit = r.read_bool()?;
+        self.irq = r.read_bool()?;
+        let pending = r.read_bool()?;
+        let val = r.read_u8()?;
+        let delay = r.read_u8()?;
+        self.pending_write = pending.then_some((val, delay));
+        Ok(())
+    }
+}
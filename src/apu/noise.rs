@@ -0,0 +1,99 @@
+use crate::apu::{Envelope, LengthCounter};
+use crate::rom::Region;
+use crate::savestate::{StateReader, StateWriter};
+
+// Timer periods in CPU cycles, indexed by the low 4 bits of $400E
+const NTSC_PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_PERIODS: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+// The noise channel ($400C-$400F): pseudo-random bits from a 15-bit linear feedback shift
+// register. In short mode the feedback comes from bit 6 instead of bit 1, which makes a sequence
+// only 93 bits long, for a metallic tone.
+pub struct Noise {
+    periods: &'static [u16; 16],
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+    short_mode: bool,
+    timer_period: u16,
+    timer: u16,
+    shift_register: u16,
+}
+
+impl Noise {
+    pub fn new(region: Region) -> Self {
+        Self {
+            periods: match region {
+                Region::Ntsc => &NTSC_PERIODS,
+                Region::Pal => &PAL_PERIODS,
+            },
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            short_mode: false,
+            timer_period: NTSC_PERIODS[0] - 1,
+            timer: 0,
+            shift_register: 1,
+        }
+    }
+
+    pub fn write_register(&mut self, reg: u16, val: u8) {
+        match reg {
+            0 => {
+                self.length.halted = val & 0x20 != 0;
+                self.envelope.write_control(val);
+            }
+            1 => {}
+            2 => {
+                self.short_mode = val & 0x80 != 0;
+                self.timer_period = self.periods[usize::from(val & 0x0F)] - 1;
+            }
+            _ => {
+                self.length.load(val >> 3);
+                self.envelope.restart();
+            }
+        }
+    }
+
+    // Clocked every CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+
+            let tap = if self.short_mode { 6 } else { 1 };
+            let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 0x1;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.shift_register & 0x1 == 0 && self.length.active() {
+            self.envelope.output()
+        } else {
+            0
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        self.envelope.save_state(w);
+        self.length.save_state(w);
+        w.write_bool(self.short_mode);
+        w.write_u16(self.timer_period);
+        w.write_u16(self.timer);
+        w.write_u16(self.shift_register);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.envelope.load_state(r)?;
+        self.length.load_state(r)?;
+        self.short_mode = r.read_bool()?;
+        self.timer_period = r.read_u16()?;
+        self.timer = r.read_u16()?;
+        self.shift_register = r.read_u16()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use crate::apu::{Envelope, LengthCounter};
+use crate::savestate::{StateReader, StateWriter};
+
+// The waveforms for each duty cycle (12.5%, 25%, 50% and 25% negated), in the order the sequencer
+// steps through them.
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+// One of the two square wave channels ($4000-$4003 and $4004-$4007). The sweep unit can bend the
+// pitch up or down by adjusting the timer period every half frame.
+pub struct Pulse {
+    ones_complement: bool, // pulse 1's sweep negates with ones' complement instead of two's
+    duty: u8,
+    sequence_step: u8,
+    timer_period: u16,
+    timer: u16,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_divider: u8,
+    sweep_reload: bool,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Self {
+            ones_complement,
+            duty: 0,
+            sequence_step: 0,
+            timer_period: 0,
+            timer: 0,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_divider: 0,
+            sweep_reload: false,
+        }
+    }
+
+    pub fn write_register(&mut self, reg: u16, val: u8) {
+        match reg {
+            0 => {
+                self.duty = val >> 6;
+                self.length.halted = val & 0x20 != 0;
+                self.envelope.write_control(val);
+            }
+            1 => {
+                self.sweep_enabled = val & 0x80 != 0;
+                self.sweep_period = (val >> 4) & 0x7;
+                self.sweep_negate = val & 0x08 != 0;
+                self.sweep_shift = val & 0x7;
+                self.sweep_reload = true;
+            }
+            2 => self.timer_period = (self.timer_period & 0x700) | u16::from(val),
+            _ => {
+                self.timer_period = (self.timer_period & 0xFF) | (u16::from(val & 0x7) << 8);
+                self.length.load(val >> 3);
+                self.sequence_step = 0;
+                self.envelope.restart();
+            }
+        }
+    }
+
+    // Clocked every other CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_step = self.sequence_step.wrapping_sub(1) & 0x7;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    // The period the sweep is heading for. This is worked out all the time, not just when the sweep
+    // is enabled, because it can mute the channel either way.
+    fn sweep_target(&self) -> i32 {
+        let period = i32::from(self.timer_period);
+        let change = period >> self.sweep_shift;
+        if self.sweep_negate {
+            period - change - i32::from(self.ones_complement)
+        } else {
+            period + change
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x7FF
+    }
+
+    // Clocked every half frame
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.muted() {
+            self.timer_period = self.sweep_target() as u16;
+        }
+
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        let high = DUTY_TABLE[usize::from(self.duty)][usize::from(self.sequence_step)] != 0;
+        if high && self.length.active() && !self.muted() {
+            self.envelope.output()
+        } else {
+            0
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.duty);
+        w.write_u8(self.sequence_step);
+        w.write_u16(self.timer_period);
+        w.write_u16(self.timer);
+        self.envelope.save_state(w);
+        self.length.save_state(w);
+        w.write_bool(self.sweep_enabled);
+        w.write_u8(self.sweep_period);
+        w.write_bool(self.sweep_negate);
+        w.write_u8(self.sweep_shift);
+        w.write_u8(self.sweep_divider);
+        w.write_bool(self.sweep_reload);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.duty = r.read_u8()?;
+        self.sequence_step = r.read_u8()?;
+        self.timer_period = r.read_u16()?;
+        self.timer = r.read_u16()?;
+        self.envelope.load_state(r)?;
+        self.length.load_state(r)?;
+        self.sweep_enabled = r.read_bool()?;
+        self.sweep_period = r.read_u8()?;
+        self.sweep_negate = r.read_bool()?;
+        self.sweep_shift = r.read_u8()?;
+        self.sweep_divider = r.read_u8()?;
+        self.sweep_reload = r.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use std::f64::consts::PI;
+
+// Sub-sample positions the step kernel is computed for
+const PHASES: usize = 64;
+// Output samples each step is spread over
+const TAPS: usize = 16;
+// Holds the deltas for the samples a step can still land on; a power of two at least TAPS
+const RING_SIZE: usize = 32;
+// Cutoff of the band-limiting, as a fraction of the output sample rate; a little under Nyquist
+const CUTOFF: f64 = 0.45;
+
+// Band-limited resampling of the APU's output from the CPU clock (about 1.79MHz) down to the
+// audio sample rate. Averaging the ~40 cycles that go into each sample would let everything above
+// half the sample rate alias back down as noise, so instead every change in the output is added
+// as a band-limited step: a windowed sinc impulse, spread over the samples around when the change
+// happened, which the output integrates. The channels change level rarely compared to the CPU
+// clock, so this is cheap.
+pub struct Resampler {
+    kernel: Vec<[f32; TAPS]>, // one impulse per phase
+    clock_rate: f64,
+    sample_rate: u32,
+    rate_adjustment: f64,
+    samples_per_cycle: f64,
+    time: f64, // position of the current input cycle between output samples, from 0.0 to 1.0
+    ring: [f32; RING_SIZE], // deltas, starting with the output sample being built
+    ring_start: usize,
+    level: f32, // the input's value as of the last cycle
+    output: f32,
+}
+
+impl Resampler {
+    // clock_rate is the rate, in Hz, input comes in at.
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Self {
+        let mut res = Self {
+            kernel: Self::kernel(),
+            clock_rate,
+            sample_rate,
+            rate_adjustment: 1.0,
+            samples_per_cycle: 0.0,
+            time: 0.0,
+            ring: [0.0; RING_SIZE],
+            ring_start: 0,
+            level: 0.0,
+            output: 0.0,
+        };
+
+        res.update_ratio();
+
+        res
+    }
+
+    // A Blackman-windowed sinc impulse for each phase, normalized so a step always adds up to its
+    // full height.
+    fn kernel() -> Vec<[f32; TAPS]> {
+        (0..PHASES)
+            .map(|phase| {
+                let offset = phase as f64 / PHASES as f64;
+                let mut taps = [0.0f64; TAPS];
+                for (i, tap) in taps.iter_mut().enumerate() {
+                    // Distance, in output samples, from the center of the impulse
+                    let x = i as f64 - offset - (TAPS / 2) as f64 + 1.0;
+                    let sinc = if x == 0.0 {
+                        1.0
+                    } else {
+                        (2.0 * PI * CUTOFF * x).sin() / (2.0 * PI * CUTOFF * x)
+                    };
+                    let w = (x / TAPS as f64 + 0.5).clamp(0.0, 1.0);
+                    let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+                    *tap = sinc * window;
+                }
+
+                let sum: f64 = taps.iter().sum();
+                let mut res = [0.0f32; TAPS];
+                for (out, tap) in res.iter_mut().zip(taps.iter()) {
+                    *out = (tap / sum) as f32;
+                }
+                res
+            })
+            .collect()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Produce samples slightly faster (above 1.0) or slower than the sample rate, so whatever is
+    // playing them can keep its buffer from running dry or filling up.
+    pub fn set_rate_adjustment(&mut self, rate_adjustment: f64) {
+        self.rate_adjustment = rate_adjustment;
+        self.update_ratio();
+    }
+
+    fn update_ratio(&mut self) {
+        self.samples_per_cycle =
+            f64::from(self.sample_rate) * self.rate_adjustment / self.clock_rate;
+    }
+
+    // Take the input for one cycle, adding any output samples it completes to samples.
+    pub fn clock(&mut self, input: f32, samples: &mut Vec<f32>) {
+        if input != self.level {
+            let delta = input - self.level;
+            self.level = input;
+
+            let phase = (self.time * PHASES as f64) as usize;
+            for (i, tap) in self.kernel[phase].iter().enumerate() {
+                self.ring[(self.ring_start + i) & (RING_SIZE - 1)] += delta * tap;
+            }
+        }
+
+        self.time += self.samples_per_cycle;
+        while self.time >= 1.0 {
+            self.time -= 1.0;
+
+            self.output += self.ring[self.ring_start];
+            self.ring[self.ring_start] = 0.0;
+            self.ring_start = (self.ring_start + 1) & (RING_SIZE - 1);
+            samples.push(self.output);
+        }
+    }
+}
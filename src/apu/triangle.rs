@@ -0,0 +1,103 @@
+use crate::apu::LengthCounter;
+use crate::savestate::{StateReader, StateWriter};
+
+// The triangle wave channel ($4008-$400B). It has no volume control, but a linear counter that
+// can silence it with finer resolution than the length counter.
+pub struct Triangle {
+    pub length: LengthCounter,
+    control: bool, // halts the length counter and keeps the linear counter reloading
+    linear_counter_period: u8,
+    linear_counter: u8,
+    linear_counter_reload: bool,
+    timer_period: u16,
+    timer: u16,
+    sequence_step: u8, // 0-31; the output goes 15 down to 0, then 0 up to 15
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Self {
+            length: LengthCounter::default(),
+            control: false,
+            linear_counter_period: 0,
+            linear_counter: 0,
+            linear_counter_reload: false,
+            timer_period: 0,
+            timer: 0,
+            sequence_step: 0,
+        }
+    }
+
+    pub fn write_register(&mut self, reg: u16, val: u8) {
+        match reg {
+            0 => {
+                self.control = val & 0x80 != 0;
+                self.length.halted = self.control;
+                self.linear_counter_period = val & 0x7F;
+            }
+            1 => {}
+            2 => self.timer_period = (self.timer_period & 0x700) | u16::from(val),
+            _ => {
+                self.timer_period = (self.timer_period & 0xFF) | (u16::from(val & 0x7) << 8);
+                self.length.load(val >> 3);
+                self.linear_counter_reload = true;
+            }
+        }
+    }
+
+    // Clocked every CPU cycle. The sequencer stops, holding its output, while either counter is 0.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length.active() && self.linear_counter > 0 {
+                self.sequence_step = (self.sequence_step + 1) & 0x1F;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    // Clocked every quarter frame
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_counter_reload {
+            self.linear_counter = self.linear_counter_period;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+
+        if !self.control {
+            self.linear_counter_reload = false;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.sequence_step < 16 {
+            15 - self.sequence_step
+        } else {
+            self.sequence_step - 16
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        self.length.save_state(w);
+        w.write_bool(self.control);
+        w.write_u8(self.linear_counter_period);
+        w.write_u8(self.linear_counter);
+        w.write_bool(self.linear_counter_reload);
+        w.write_u16(self.timer_period);
+        w.write_u16(self.timer);
+        w.write_u8(self.sequence_step);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.length.load_state(r)?;
+        self.control = r.read_bool()?;
+        self.linear_counter_period = r.read_u8()?;
+        self.linear_counter = r.read_u8()?;
+        self.linear_counter_reload = r.read_bool()?;
+        self.timer_period = r.read_u16()?;
+        self.timer = r.read_u16()?;
+        self.sequence_step = r.read_u8()?;
+        Ok(())
+    }
+}
@@ -1,36 +1,195 @@
+use crate::apu::Apu;
 use crate::controller::Controller;
+use crate::mapper::Mapper;
 use crate::ppu::Ppu;
-use crate::rom::Rom;
+use crate::savestate::{StateReader, StateWriter};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Devices that can pull the CPU's IRQ line low. The line is wired-OR: it stays asserted for as
+// long as any of them holds it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IrqSource {
+    FrameCounter, // APU frame counter
+    Dmc,          // APU delta modulation channel
+    Mapper,
+    Expansion, // devices on the expansion port
+}
+
+impl IrqSource {
+    fn mask(self) -> u8 {
+        1 << (self as u8)
+    }
+}
+
+// The PPU runs this many master clock ticks behind the CPU, which lines up its dots with the CPU's
+// reads and writes the way they are on a real console.
+const PPU_OFFSET: u64 = 1;
 
 pub struct Bus {
     pub ram: [u8; 0x2000],
-    pub rom: Rom,
+    pub mapper: Rc<RefCell<dyn Mapper>>, // shared with the PPU, which reads CHR through it
     pub ppu: Ppu,
+    pub apu: Apu,
     pub controller: Controller,
-    pub dma_in_progress: bool,
+    pub dma_page: Option<u8>, // OAM DMA requested through $4014, run on the CPU's next read
+    pub open_bus: u8, // last value on the CPU data bus, returned for reads of unmapped addresses
+    pub irq_sources: u8, // one bit per IrqSource currently asserting the IRQ line
+    pub master_clock: u64, // master clock ticks since power on
+    pub ppu_clock: u64, // master clock tick the PPU has been run up to
+    pub frame_complete: bool, // set when the PPU finishes a frame, cleared by whoever runs the console
 }
 
 impl Bus {
-    pub fn new(rom: Rom, ppu: Ppu, controller: Controller) -> Self {
-        let mut res = Self {
+    // The PPU must have been built with the same mapper.
+    pub fn new(
+        mapper: Rc<RefCell<dyn Mapper>>,
+        ppu: Ppu,
+        apu: Apu,
+        controller: Controller,
+    ) -> Self {
+        Self {
             ram: [0u8; 0x2000],
-            rom,
+            mapper,
             ppu,
+            apu,
             controller,
-            dma_in_progress: false,
-        };
+            dma_page: None,
+            open_bus: 0x0,
+            irq_sources: 0x0,
+            master_clock: 0,
+            ppu_clock: 0,
+            frame_complete: false,
+        }
+    }
+
+    // Swap in a different cartridge. The console should be power cycled afterwards.
+    pub fn insert_cartridge(&mut self, mapper: Rc<RefCell<dyn Mapper>>) {
+        self.ppu.mapper = Rc::clone(&mapper);
+        self.mapper = mapper;
+    }
+
+    pub fn power_cycle(&mut self) {
+        self.ram = [0u8; 0x2000];
+        self.dma_page = None;
+        self.open_bus = 0x0;
+        self.irq_sources = 0x0;
+        self.master_clock = 0;
+        self.ppu_clock = 0;
+        self.frame_complete = false;
+        self.ppu.power_on();
+        self.apu.power_on();
+        self.mapper.borrow_mut().power_cycle();
+    }
+
+    pub fn reset(&mut self) {
+        self.dma_page = None;
+        self.ppu.reset();
+        self.apu.reset();
+        self.mapper.borrow_mut().reset();
+    }
+
+    pub fn set_irq(&mut self, source: IrqSource, asserted: bool) {
+        if asserted {
+            self.irq_sources |= source.mask();
+        } else {
+            self.irq_sources &= !source.mask();
+        }
+    }
+
+    // Whether anything is asserting the IRQ line.
+    pub fn irq(&self) -> bool {
+        self.irq_sources != 0
+    }
+
+    // Read from the CPU's address space, taking one CPU cycle. The read happens a little before
+    // the middle of the cycle, as far as the PPU is concerned.
+    pub fn read(&mut self, addr: u16) -> u8 {
+        let half_cycle = self.ppu.region.cpu_divider() / 2;
+
+        self.run_master_clock(half_cycle - 1);
+        let val = self.get_byte_at(addr);
+        self.run_master_clock(half_cycle + 1);
+        self.end_cpu_cycle();
+
+        val
+    }
+
+    // Write to the CPU's address space, taking one CPU cycle. The write happens a little after
+    // the middle of the cycle, as far as the PPU is concerned.
+    pub fn write(&mut self, addr: u16, val: u8) {
+        let half_cycle = self.ppu.region.cpu_divider() / 2;
 
-        for i in 0..res.rom.chr_rom.len() {
-            res.ppu.vram[i] = res.rom.chr_rom[i];
+        self.run_master_clock(half_cycle + 1);
+        self.set_byte_at(addr, val);
+        self.run_master_clock(half_cycle - 1);
+        self.end_cpu_cycle();
+    }
+
+    // Advance the master clock, running the PPU up to it.
+    fn run_master_clock(&mut self, ticks: u64) {
+        self.master_clock += ticks;
+
+        let ppu_divider = self.ppu.region.ppu_divider();
+        while self.ppu_clock + ppu_divider + PPU_OFFSET <= self.master_clock {
+            self.frame_complete |= self.ppu.step();
+            self.ppu_clock += ppu_divider;
         }
+    }
 
-        res.ppu.mirror_type = res.rom.mirroring;
+    // A cycle the CPU spends halted without reading anything, as far as the devices on the bus
+    // can tell.
+    pub fn idle(&mut self) {
+        self.run_master_clock(self.ppu.region.cpu_divider());
+        self.end_cpu_cycle();
+    }
+
+    // Clock the devices on the bus that count CPU cycles.
+    fn end_cpu_cycle(&mut self) {
+        self.apu.tick();
+        self.set_irq(IrqSource::FrameCounter, self.apu.frame_counter.irq);
+        self.set_irq(IrqSource::Dmc, self.apu.dmc.irq);
 
-        res
+        let mapper_irq = {
+            let mut mapper = self.mapper.borrow_mut();
+            mapper.cpu_tick();
+            mapper.irq()
+        };
+        self.set_irq(IrqSource::Mapper, mapper_irq);
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.ram);
+        w.write_bool(self.dma_page.is_some());
+        w.write_u8(self.dma_page.unwrap_or(0));
+        w.write_u8(self.open_bus);
+        w.write_u8(self.irq_sources);
+        w.write_u64(self.master_clock);
+        w.write_u64(self.ppu_clock);
+        self.mapper.borrow().save_state(w);
+        self.ppu.save_state(w);
+        self.apu.save_state(w);
+        self.controller.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        r.read_bytes(&mut self.ram)?;
+        let dma_pending = r.read_bool()?;
+        let dma_page = r.read_u8()?;
+        self.dma_page = dma_pending.then_some(dma_page);
+        self.open_bus = r.read_u8()?;
+        self.irq_sources = r.read_u8()?;
+        self.master_clock = r.read_u64()?;
+        self.ppu_clock = r.read_u64()?;
+        self.mapper.borrow_mut().load_state(r)?;
+        self.ppu.load_state(r)?;
+        self.apu.load_state(r)?;
+        self.controller.load_state(r)
     }
 
     pub fn get_byte_at(&mut self, addr: u16) -> u8 {
-        match addr {
+        let val = match addr {
             // 0x0000-0x07FF mirrored at 0x0800, 0x1000, and 0x18000.
             0x0000..=0x1FFF => {
                 let actual_addr = addr % 0x0800;
@@ -40,18 +199,22 @@ impl Bus {
                 // 0x2000-0x2007 mirrored in 0x2000-0x4000
                 let actual_addr = ((addr - 0x2000) % 0x8) + 0x2000;
                 match actual_addr {
-                    0x2000 => self.ppu.ppuctrl,
-                    0x2001 => self.ppu.ppumask,
+                    // Write-only registers read back whatever was last written to the PPU
+                    0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 => self.ppu.io_latch,
                     // Reading from ppustatus register clears bit 7 (v-blank)
                     0x2002 => {
+                        // Reading on the dot before vblank starts sees it clear, and stops it from
+                        // being set (and raising an NMI) this frame
+                        if self.ppu.scanline == 241 && self.ppu.cycle == 1 {
+                            self.ppu.suppress_vblank = true;
+                        }
+
                         let result = self.ppu.ppustatus;
                         self.ppu.ppustatus &= 0b01111111; // clear vblank when we read 0x2002
                         self.ppu.two_write_partial = false; // clear the partial write latch used for ppuscroll/ppuaddr
                         result
                     }
-                    0x2003 => panic!("Not allowed to read from 0x2003"),
                     0x2004 => self.ppu.get_oam_byte_at(self.ppu.oamaddr),
-                    0x2005..=0x2006 => panic!("Not allowed to read from 0x{:04X}", addr),
                     0x2007 => {
                         let addr = self.ppu.ppuaddr;
                         let data = self.ppu.get_vram_byte_at(self.ppu.ppuaddr);
@@ -92,11 +255,13 @@ impl Bus {
                             data
                         };
 
-                        self.ppu.ppuaddr += if (self.ppu.ppuctrl & (1 << 2)) == 0 {
+                        let increment = if (self.ppu.ppuctrl & (1 << 2)) == 0 {
                             0x1
                         } else {
                             0x20
                         };
+                        // v is 15 bits, and wraps
+                        self.ppu.ppuaddr = self.ppu.ppuaddr.wrapping_add(increment) & 0x7FFF;
 
                         returned_data
                     }
@@ -105,35 +270,33 @@ impl Bus {
             }
             0x4000..=0x4017 => {
                 match addr {
-                    0x4015 => 0,
+                    // Bit 5 isn't driven
+                    0x4015 => self.apu.read_status() | (self.open_bus & 0x20),
                     0x4016 => self.controller.read() as u8,
                     0x4017 => {
                         //ignore read from controller 2
                         0
                     }
-                    _ => panic!("Don't know how to read from 0x{:05x}", addr),
-                }
-            }
-            0x4020..=0x7999 => 0x00, // emulate open bus behavior
-            0x8000..=0xFFFF => {
-                let mut rom_access_addr = addr - 0x8000;
-                if self.rom.prg_rom.len() <= 0x4000 {
-                    //if only one rom bank, should be mirrored
-                    rom_access_addr %= 0x4000;
+                    // The APU registers are write-only
+                    _ => self.open_bus,
                 }
-                self.rom.prg_rom[usize::from(rom_access_addr)]
             }
-            _ => panic!("Don't know how to read from 0x{:05x}", addr),
-        }
-    }
+            0x4018..=0x401F => self.open_bus, // emulate open bus behavior
+            // Cartridge space
+            0x4020..=0xFFFF => self
+                .mapper
+                .borrow_mut()
+                .cpu_read(addr)
+                .unwrap_or(self.open_bus),
+        };
 
-    pub fn get_word_at(&mut self, addr: u16) -> u16 {
-        let low = self.get_byte_at(addr);
-        let high = self.get_byte_at(addr + 1);
-        ((high as u16) << 8) | (low as u16)
+        self.open_bus = val;
+        val
     }
 
     pub fn set_byte_at(&mut self, addr: u16, val: u8) {
+        self.open_bus = val;
+
         match addr {
             // 0x0000-0x07FF mirrored at 0x0800, 0x1000, and 0x18000.
             0x0000..=0x1FFF => {
@@ -143,6 +306,7 @@ impl Bus {
             0x2000..=0x3FFF => {
                 // 0x2000-0x2007 mirrored in 0x2000-0x4000
                 let actual_addr = ((addr - 0x2000) % 0x8) + 0x2000;
+                self.ppu.io_latch = val;
                 match actual_addr {
                     0x2000 => {
                         self.ppu.ppuctrl = val;
@@ -181,49 +345,42 @@ impl Bus {
                             self.ppu.ppuscroll &= !0xFF; // clear bits 1-8 of ppuscroll
                             self.ppu.ppuscroll |= (val as u16) & 0xFF; // assign bits 1-8 of val to bits 1-8 of ppuscroll
                             self.ppu.ppuaddr = self.ppu.ppuscroll; // on write two of $2006, assign ppuscroll to ppuaddr (t to v)
+
+                            // The new address goes straight out on the PPU's address bus
+                            self.mapper
+                                .borrow_mut()
+                                .ppu_address(self.ppu.ppuaddr % 0x4000);
                         };
                         self.ppu.two_write_partial = !self.ppu.two_write_partial;
                     }
                     0x2007 => {
-                        assert!(!self.ppu.two_write_partial);
                         self.ppu.set_vram_byte_at(self.ppu.ppuaddr, val);
 
-                        self.ppu.ppuaddr += if (self.ppu.ppuctrl & (1 << 2)) == 0 {
+                        let increment = if (self.ppu.ppuctrl & (1 << 2)) == 0 {
                             0x1
                         } else {
                             0x20
                         };
+                        // v is 15 bits, and wraps
+                        self.ppu.ppuaddr = self.ppu.ppuaddr.wrapping_add(increment) & 0x7FFF;
                     }
-                    _ => panic!(),
+                    _ => unreachable!(),
                 }
             }
             0x4000..=0x4017 => {
                 match addr {
-                    // Direct memory access (DMA)
-                    0x4014 => {
-                        let start_addr = (val as u16) << 8;
-                        for i in 0..=255 {
-                            let data = self.get_byte_at(start_addr + u16::from(i));
-                            self.ppu
-                                .set_oam_byte_at(self.ppu.oamaddr.wrapping_add(i), data);
-                        }
-                        self.dma_in_progress = true;
-                    }
+                    0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write_register(addr, val),
+                    // Direct memory access (DMA). The CPU does the copying, through $2004.
+                    0x4014 => self.dma_page = Some(val),
                     0x4016 => {
                         self.controller.set_strobe(val & 0x1 != 0);
                     }
                     _ => {}
                 }
             }
-            0x8000..=0xFFFF => {
-                let mut rom_access_addr = addr - 0x8000;
-                if self.rom.prg_rom.len() <= 0x4000 {
-                    //if only one rom bank, should be mirrored
-                    rom_access_addr %= 0x4000;
-                }
-                self.rom.prg_rom[usize::from(rom_access_addr)] = val;
-            }
-            _ => print!("{}", char::from(val)),
+            0x4018..=0x401F => {}
+            // Cartridge space
+            0x4020..=0xFFFF => self.mapper.borrow_mut().cpu_write(addr, val),
         }
     }
 
@@ -1,20 +1,69 @@
-use sdl2::keyboard::{Keycode, Scancode};
+use crate::frontend::InputSource;
+use crate::movie::Movie;
+use crate::savestate::{StateReader, StateWriter};
 
-use std::cell::RefCell;
-use std::rc::Rc;
+// What advancing movie playback by a frame leads to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieStep {
+    Continue,
+    SoftReset,       // the movie presses the console's reset button on the new frame
+    Finished(usize), // the movie ran out of input on the new frame, after this many frames
+}
 
 pub struct Controller {
-    input: Rc<RefCell<sdl2::EventPump>>,
+    input: Box<dyn InputSource>,
     strobe: bool, // strobe tracks whether this controller is "latched" or not
     state: Vec<bool>,
+    movie: Option<Movie>,
+    movie_frame: usize,
 }
 
 impl Controller {
-    pub fn new(input: Rc<RefCell<sdl2::EventPump>>) -> Self {
+    pub fn new(input: Box<dyn InputSource>) -> Self {
         Self {
             input,
             strobe: false,
             state: Vec::new(),
+            movie: None,
+            movie_frame: 0,
+        }
+    }
+
+    // While a movie is playing, button state comes from the movie instead of the keyboard.
+    pub fn set_movie(&mut self, movie: Movie) {
+        self.movie = Some(movie);
+        self.movie_frame = 0;
+    }
+
+    // Advance movie playback by one frame. Once the movie runs out, input falls back to the input
+    // source.
+    pub fn next_movie_frame(&mut self) -> MovieStep {
+        let movie = match &self.movie {
+            Some(movie) => movie,
+            None => return MovieStep::Continue,
+        };
+
+        let was_playing = movie.frame(self.movie_frame).is_some();
+        self.movie_frame += 1;
+
+        match movie.frame(self.movie_frame) {
+            Some(frame) if frame.soft_reset => MovieStep::SoftReset,
+            Some(_) => MovieStep::Continue,
+            None if was_playing => MovieStep::Finished(self.movie_frame),
+            None => MovieStep::Continue,
+        }
+    }
+
+    // Buttons currently held, from the movie if one is playing and from the input source
+    // otherwise.
+    fn buttons(&mut self) -> u8 {
+        match self
+            .movie
+            .as_ref()
+            .and_then(|movie| movie.frame(self.movie_frame))
+        {
+            Some(frame) => frame.buttons,
+            None => self.input.buttons(0),
         }
     }
 
@@ -39,37 +88,40 @@ impl Controller {
             true => self.strobe = true,
             false => {
                 self.strobe = false;
-                self.state = [
-                    Keycode::Z,      // A
-                    Keycode::X,      // B
-                    Keycode::RShift, // SELECT
-                    Keycode::Return, // START
-                    Keycode::Up,     // UP
-                    Keycode::Down,   // DOWN
-                    Keycode::Left,   // LEFT
-                    Keycode::Right,  // RIGHT
-                ]
-                .iter()
-                .map(|&keycode| {
-                    self.input
-                        .borrow()
-                        .keyboard_state()
-                        .is_scancode_pressed(Scancode::from_keycode(keycode).unwrap())
-                })
-                .rev()
-                .collect::<Vec<_>>();
+                let buttons = self.buttons();
+                self.state = (0..8)
+                    .map(|i| (buttons >> i) & 0x1 != 0)
+                    .rev()
+                    .collect::<Vec<_>>();
             }
         }
     }
 
     pub fn read(&mut self) -> bool {
         match self.strobe {
-            true => self
-                .input
-                .borrow()
-                .keyboard_state()
-                .is_scancode_pressed(Scancode::from_keycode(Keycode::A).unwrap()),
+            // While strobe is held, reads keep returning the current state of A
+            true => self.buttons() & 0x1 != 0,
             false => self.state.pop().unwrap_or(false),
         }
     }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.strobe);
+        w.write_u8(self.state.len() as u8);
+        for &pressed in &self.state {
+            w.write_bool(pressed);
+        }
+        w.write_u64(self.movie_frame as u64);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.strobe = r.read_bool()?;
+        let len = r.read_u8()?;
+        self.state = (0..len)
+            .map(|_| r.read_bool())
+            .collect::<Result<Vec<_>, _>>()?;
+        // Keeps a playing movie in step with the state
+        self.movie_frame = r.read_u64()? as usize;
+        Ok(())
+    }
 }
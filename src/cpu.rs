@@ -1,4 +1,6 @@
 use crate::bus::Bus;
+use crate::error::EmuError;
+use crate::savestate::{StateReader, StateWriter};
 
 pub struct Cpu {
     pub pc: u16,
@@ -6,7 +8,6 @@ pub struct Cpu {
     pub accumulator: u8,
     pub x: u8,
     pub y: u8,
-    pub cycles_left: u16,
     pub cycles_completed: u64,
     pub carry: bool,
     pub zero: bool,
@@ -14,14 +15,24 @@ pub struct Cpu {
     pub decimal: bool,
     pub overflow: bool,
     pub sign: bool,
+    pub nmi_line: bool, // level of the PPU's NMI output at the end of the last cycle
+    pub nmi_detected: bool, // NMI edge seen, not yet handled
+    pub nmi_pending: bool, // NMI detected a cycle ago, taken once the current instruction finishes
+    pub irq_detected: bool, // IRQ line asserted and not masked at the end of the last cycle
+    pub irq_pending: bool, // IRQ detected a cycle ago, taken once the current instruction finishes
+    pub jam_opcode: Option<u8>, // set once the CPU has locked up on a JAM opcode, until reset
     pub bus: Bus,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Opcode {
     Add,
+    Alr,
+    Anc,
     And,
+    Arr,
     Asl,
+    Axs,
     Bcc,
     Bcs,
     Beq,
@@ -50,11 +61,13 @@ pub enum Opcode {
     Isc,
     Jmp,
     Jsr,
+    Las,
     Lax,
     Lda,
     Ldx,
     Ldy,
     Lsr,
+    Lxa,
     Nop,
     Ora,
     Pha,
@@ -72,33 +85,38 @@ pub enum Opcode {
     Sec,
     Sed,
     Sei,
+    Sha,
+    Shx,
+    Shy,
     Slo,
     Sre,
     Sta,
     Stx,
     Sty,
+    Tas,
     Tax,
     Tay,
     Tsx,
     Txa,
     Txs,
     Tya,
+    Xaa,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AddressingMode {
-    ZeroPage(u8),
-    ZeroPageX(u8),
-    ZeroPageY(u8),
-    Absolute(u16),
-    AbsoluteX(u16),
-    AbsoluteY(u16),
-    Indirect(u16),
-    IndirectX(u8),
-    IndirectY(u8),
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
     Implicit,
-    Immediate(u8),
-    Relative(i8),
+    Immediate,
+    Relative,
     Accumulator,
 }
 
@@ -106,13 +124,21 @@ pub enum AddressingMode {
 pub struct Instruction {
     pub opcode: Opcode,
     pub mode: AddressingMode,
-    pub cycles: u8,
-    pub page_cross_cost: bool,
+}
+
+// Whether an instruction reads from the address it works out, or writes to it (read-modify-write
+// instructions count as writes). Indexed writes always spend a cycle fixing up the high byte of
+// the address; indexed reads only do when the index crosses a page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum Interrupt {
     Irq,   // maskable interrupt
+    Brk,   // software interrupt, through the same vector as Irq
     Nmi,   // non-maskable interrupt
     Reset, // reset interrupt
 }
@@ -125,7 +151,6 @@ impl Cpu {
             accumulator: 0x0,
             x: 0x0,
             y: 0x0,
-            cycles_left: 0x0,
             cycles_completed: 0x0,
             carry: false,
             zero: false,
@@ -133,61 +158,259 @@ impl Cpu {
             decimal: false,
             overflow: false,
             sign: false,
+            nmi_line: false,
+            nmi_detected: false,
+            nmi_pending: false,
+            irq_detected: false,
+            irq_pending: false,
+            jam_opcode: None,
             bus,
         }
     }
 
-    pub fn step(&mut self) {
-        if self.cycles_left > 0 {
-            self.cycles_left -= 1;
-            self.cycles_completed += 1;
-            return;
+    // Run one instruction, and then the interrupt sequence if an interrupt was polled during it.
+    // Every bus access advances the rest of the console by one CPU cycle.
+    pub fn step(&mut self) -> Result<(), EmuError> {
+        if let Some(opcode) = self.jam_opcode {
+            return Err(EmuError::CpuJam {
+                opcode,
+                pc: self.pc,
+            });
         }
 
-        let _pc = self.pc;
-        let _processor_status: u8 = ((self.sign as u8) << 7)
-            | ((self.overflow as u8) << 6)
-            | ((1 as u8) << 5)
-            | ((1 as u8) << 4)
-            | ((self.decimal as u8) << 3)
-            | ((self.interrupt as u8) << 2)
-            | ((self.zero as u8) << 1)
-            | (self.carry as u8);
-        let _a = self.accumulator;
-        let _x = self.x;
-        let _y = self.y;
-        let _sp = self.sp;
-        if self.bus.dma_in_progress {
-            self.bus.dma_in_progress = false;
-            self.cycles_left = 513;
-            if !self.bus.ppu.even_frame {
-                self.cycles_left += 1; // on odd CPU cycles DMA takes 1 extra cycle
-            }
-        } else if self.bus.ppu.nmi_waiting {
-            self.bus.ppu.nmi_waiting = false;
+        let next_instruction = self.fetch_next_instruction()?;
+        //println!("{:04x?} -> {}", next_instruction, self.trace());
+        self.execute_instruction(next_instruction)?;
+
+        if self.nmi_pending {
             self.interrupt(Interrupt::Nmi);
-        } else {
-            let next_instruction = self.fetch_next_instruction();
-            //println!("{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{:<3} SL:{:<3} CPU Cycle:{} Status:0b{:08b}", pc, a, x, y, processor_status, sp, self.bus.ppu.cycle, self.bus.ppu.scanline, self.cycles_completed, self.bus.ppu.ppustatus);
-            //println!("{:04x?} -> {:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{:<3} SL:{:<3} CPU Cycle:{}", next_instruction, pc, a, x, y, processor_status, sp, self.bus.ppu.cycle, self.bus.ppu.scanline, self.cycles_completed);
-            self.execute_instruction(next_instruction);
-
-            self.cycles_left += u16::from(next_instruction.cycles);
-            // If instruction has an "oops" cost and the addressing mode used would incur an "oops"
-            // cost, update the cycles left here.
-            if next_instruction.page_cross_cost
-                && self.read_crosses_page_boundry(next_instruction.mode)
-            {
-                self.cycles_left += 1;
-            }
+        } else if self.irq_pending {
+            self.interrupt(Interrupt::Irq);
+        }
+
+        Ok(())
+    }
+
+    // Read from the bus, taking one cycle. If an OAM DMA or DMC sample fetch is waiting, the CPU
+    // is halted on this read until it's done.
+    fn read(&mut self, addr: u16) -> u8 {
+        if self.bus.dma_page.is_some() || self.bus.apu.dmc.dma_address().is_some() {
+            self.dma(addr);
         }
 
-        self.cycles_left -= 1;
+        let val = self.bus.read(addr);
+        self.end_cycle();
+        val
+    }
+
+    // Write to the bus, taking one cycle.
+    fn write(&mut self, addr: u16, val: u8) {
+        self.bus.write(addr, val);
+        self.end_cycle();
+    }
+
+    // The CPU polls its interrupt lines at the end of every cycle, but what it sees only matters
+    // one cycle later: an interrupt has to be there by the second-to-last cycle of an instruction
+    // to be taken when it finishes. NMI is edge-triggered, IRQ level-triggered. This is also why
+    // CLI, SEI and PLP seem to change the I flag one instruction late: they change it on their
+    // last cycle, after the poll that counts.
+    fn end_cycle(&mut self) {
         self.cycles_completed += 1;
+
+        self.nmi_pending = self.nmi_detected;
+        let nmi_line = self.bus.ppu.nmi_line();
+        if nmi_line && !self.nmi_line {
+            self.nmi_detected = true;
+        }
+        self.nmi_line = nmi_line;
+
+        self.irq_pending = self.irq_detected;
+        self.irq_detected = self.bus.irq() && !self.interrupt;
+    }
+
+    // Run the DMAs waiting to take over the bus: copying a page of memory to OAM, and fetching the
+    // DMC's next sample byte. Either one halts the CPU on the read it was about to make, and then
+    // reads on "get" (even) cycles and writes on "put" (odd) ones, waiting a cycle to line up if it
+    // has to. A DMC fetch needs a halt cycle and a dummy cycle before its read, which can overlap
+    // the OAM DMA's reads and writes, so a fetch during an OAM DMA only costs a cycle or two.
+    fn dma(&mut self, halted_addr: u16) {
+        let oam_page = self.bus.dma_page.take();
+        let mut oam_cycle: u16 = if oam_page.is_some() { 0 } else { 512 }; // read, write, ... 512 is done
+        let mut oam_data = 0;
+        let mut dmc_wait: Option<u8> = None; // cycles until the DMC fetch can read, if there's one
+
+        // The controllers only see the first of a run of reads, so the dummy reads after the halt
+        // don't clock them again. The read the CPU goes back to does, which is why DMC fetches
+        // can make controller reads lose a bit.
+        let dummy_reads = halted_addr != 0x4016 && halted_addr != 0x4017;
+
+        // The halt cycle repeats the read the CPU was about to make
+        if self.bus.apu.dmc.dma_address().is_some() {
+            dmc_wait = Some(1);
+        }
+        self.bus.read(halted_addr);
+        self.end_cycle();
+
+        loop {
+            let dmc_address = self.bus.apu.dmc.dma_address();
+            if dmc_address.is_none() {
+                dmc_wait = None;
+            } else if dmc_wait.is_none() {
+                dmc_wait = Some(2);
+            }
+            if dmc_wait.is_none() && oam_cycle == 512 {
+                break;
+            }
+
+            let get_cycle = self.cycles_completed.is_multiple_of(2);
+            if let (true, Some(0), Some(addr)) = (get_cycle, dmc_wait, dmc_address) {
+                let val = self.bus.read(addr);
+                self.end_cycle();
+                self.bus.apu.dmc.fill_sample_buffer(val);
+                dmc_wait = None;
+                continue;
+            }
+
+            match oam_page {
+                Some(page) if get_cycle && oam_cycle < 512 && oam_cycle.is_multiple_of(2) => {
+                    oam_data = self.bus.read((u16::from(page) << 8) | (oam_cycle / 2));
+                    self.end_cycle();
+                    oam_cycle += 1;
+                }
+                Some(_) if !get_cycle && oam_cycle < 512 && oam_cycle % 2 == 1 => {
+                    self.write(0x2004, oam_data);
+                    oam_cycle += 1;
+                }
+                // Waiting to line up with a get or put cycle
+                _ if dummy_reads => {
+                    self.bus.read(halted_addr);
+                    self.end_cycle();
+                }
+                _ => {
+                    self.bus.idle();
+                    self.end_cycle();
+                }
+            }
+
+            if let Some(wait) = &mut dmc_wait {
+                *wait = wait.saturating_sub(1);
+            }
+        }
+    }
+
+    // Processor status register, as pushed by php (with the B flag set).
+    pub fn status(&self) -> u8 {
+        ((self.sign as u8) << 7)
+            | ((self.overflow as u8) << 6)
+            | (1_u8 << 5)
+            | (1_u8 << 4)
+            | ((self.decimal as u8) << 3)
+            | ((self.interrupt as u8) << 2)
+            | ((self.zero as u8) << 1)
+            | (self.carry as u8)
+    }
+
+    // One line summary of the CPU registers and PPU position, in the style of nestest's log.
+    pub fn trace(&self) -> String {
+        format!(
+            "{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{:<3} SL:{:<3} CPU Cycle:{}",
+            self.pc,
+            self.accumulator,
+            self.x,
+            self.y,
+            self.status(),
+            self.sp,
+            self.bus.ppu.cycle,
+            self.bus.ppu.scanline,
+            self.cycles_completed
+        )
+    }
+
+    // Return the CPU and everything on the bus to their power-on state, then run the reset
+    // sequence.
+    pub fn power_cycle(&mut self) {
+        self.pc = 0x0;
+        self.sp = 0x0;
+        self.accumulator = 0x0;
+        self.x = 0x0;
+        self.y = 0x0;
+        self.cycles_completed = 0x0;
+        self.carry = false;
+        self.zero = false;
+        self.interrupt = false;
+        self.decimal = false;
+        self.overflow = false;
+        self.sign = false;
+        self.nmi_line = false;
+        self.nmi_detected = false;
+        self.nmi_pending = false;
+        self.irq_detected = false;
+        self.irq_pending = false;
+        self.jam_opcode = None;
+        self.bus.power_cycle();
+        self.interrupt(Interrupt::Reset);
+    }
+
+    // Equivalent to pressing the console's reset button.
+    pub fn reset(&mut self) {
+        self.nmi_detected = false;
+        self.nmi_pending = false;
+        self.irq_detected = false;
+        self.irq_pending = false;
+        self.jam_opcode = None;
+        self.bus.reset();
+        self.interrupt(Interrupt::Reset);
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(self.pc);
+        w.write_u8(self.sp);
+        w.write_u8(self.accumulator);
+        w.write_u8(self.x);
+        w.write_u8(self.y);
+        w.write_u64(self.cycles_completed);
+        w.write_bool(self.carry);
+        w.write_bool(self.zero);
+        w.write_bool(self.interrupt);
+        w.write_bool(self.decimal);
+        w.write_bool(self.overflow);
+        w.write_bool(self.sign);
+        w.write_bool(self.nmi_line);
+        w.write_bool(self.nmi_detected);
+        w.write_bool(self.nmi_pending);
+        w.write_bool(self.irq_detected);
+        w.write_bool(self.irq_pending);
+        w.write_bool(self.jam_opcode.is_some());
+        w.write_u8(self.jam_opcode.unwrap_or(0));
+        self.bus.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.pc = r.read_u16()?;
+        self.sp = r.read_u8()?;
+        self.accumulator = r.read_u8()?;
+        self.x = r.read_u8()?;
+        self.y = r.read_u8()?;
+        self.cycles_completed = r.read_u64()?;
+        self.carry = r.read_bool()?;
+        self.zero = r.read_bool()?;
+        self.interrupt = r.read_bool()?;
+        self.decimal = r.read_bool()?;
+        self.overflow = r.read_bool()?;
+        self.sign = r.read_bool()?;
+        self.nmi_line = r.read_bool()?;
+        self.nmi_detected = r.read_bool()?;
+        self.nmi_pending = r.read_bool()?;
+        self.irq_detected = r.read_bool()?;
+        self.irq_pending = r.read_bool()?;
+        let jammed = r.read_bool()?;
+        let jam_opcode = r.read_u8()?;
+        self.jam_opcode = jammed.then_some(jam_opcode);
+        self.bus.load_state(r)
     }
 
     fn push_byte(&mut self, val: u8) {
-        self.bus.set_byte_at(0x100 + u16::from(self.sp), val);
+        self.write(0x100 + u16::from(self.sp), val);
         self.sp = self.sp.wrapping_sub(1);
     }
 
@@ -201,7 +424,7 @@ impl Cpu {
 
     fn pop_byte(&mut self) -> u8 {
         self.sp = self.sp.wrapping_add(1);
-        self.bus.get_byte_at(0x100 + u16::from(self.sp))
+        self.read(0x100 + u16::from(self.sp))
     }
 
     fn pop_word(&mut self) -> u16 {
@@ -211,1668 +434,1374 @@ impl Cpu {
         ((high_byte as u16) << 8) | low_byte as u16
     }
 
+    // The stack pointer is incremented before a pull, in a cycle that reads (and ignores) the top
+    // of the stack.
+    fn dummy_stack_read(&mut self) {
+        self.read(0x100 + u16::from(self.sp));
+    }
+
+    fn read_word(&mut self, addr: u16) -> u16 {
+        let low_byte = self.read(addr);
+        let high_byte = self.read(addr.wrapping_add(1));
+
+        ((high_byte as u16) << 8) | low_byte as u16
+    }
+
+    // The 7-cycle interrupt sequence. BRK has already spent its first two cycles fetching the
+    // opcode and the byte after it; hardware interrupts spend them on reads they throw away. Reset
+    // goes through the motions of pushing to the stack, but the writes are turned into reads.
     pub fn interrupt(&mut self, int_type: Interrupt) {
-        self.push_word(self.pc);
+        if !matches!(int_type, Interrupt::Brk) {
+            self.read(self.pc);
+            self.read(self.pc);
+        }
 
-        // Push processor flags to stack
-        self.php();
+        if let Interrupt::Nmi = int_type {
+            self.nmi_detected = false;
+        }
 
-        self.interrupt = true;
-        self.pc = match int_type {
-            Interrupt::Irq => self.bus.get_word_at(0xFFFE),
-            Interrupt::Nmi => self.bus.get_word_at(0xFFFA),
-            Interrupt::Reset => self.bus.get_word_at(0xFFFC),
+        if let Interrupt::Reset = int_type {
+            for _ in 0..3 {
+                self.dummy_stack_read();
+                self.sp = self.sp.wrapping_sub(1);
+            }
+        } else {
+            self.push_word(self.pc);
+
+            // Push processor flags to stack. Only BRK pushes them with the B flag set, which is
+            // how an interrupt handler can tell it apart from an IRQ.
+            match int_type {
+                Interrupt::Brk => self.push_byte(self.status()),
+                _ => self.push_byte(self.status() & !(1 << 4)),
+            }
+        }
+
+        // An NMI that comes in while an IRQ or BRK is pushing to the stack hijacks it: the CPU
+        // fetches the NMI vector instead, and the NMI isn't taken again afterwards.
+        let vector = match int_type {
+            Interrupt::Irq | Interrupt::Brk if self.nmi_detected => {
+                self.nmi_detected = false;
+                0xFFFA
+            }
+            Interrupt::Irq | Interrupt::Brk => 0xFFFE,
+            Interrupt::Nmi => 0xFFFA,
+            Interrupt::Reset => 0xFFFC,
         };
 
-        self.cycles_left = 7;
+        self.interrupt = true;
+        self.pc = self.read_word(vector);
+        self.nmi_pending = false;
     }
 
-    pub fn fetch_next_instruction(&mut self) -> Instruction {
-        let opcode: u8 = self.bus.get_byte_at(self.pc);
-        let byte_after_opcode: u8 = self.bus.get_byte_at(self.pc + 1);
-        let signed_byte_after_opcode: i8 = byte_after_opcode as i8;
-        let word_after_opcode: u16 = self.bus.get_word_at(self.pc + 1);
+    pub fn fetch_next_instruction(&mut self) -> Result<Instruction, EmuError> {
+        let opcode: u8 = self.read(self.pc);
         //println!("Opcode: 0x{:02x}", opcode);
         let result = match opcode {
             /* Add */
             0x69 => Instruction {
                 opcode: Opcode::Add,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0x65 => Instruction {
                 opcode: Opcode::Add,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x75 => Instruction {
                 opcode: Opcode::Add,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x6D => Instruction {
                 opcode: Opcode::Add,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x7D => Instruction {
                 opcode: Opcode::Add,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0x79 => Instruction {
                 opcode: Opcode::Add,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteY,
             },
             0x61 => Instruction {
                 opcode: Opcode::Add,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x71 => Instruction {
                 opcode: Opcode::Add,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: true,
+                mode: AddressingMode::IndirectY,
+            },
+            /* Alr */
+            0x4B => Instruction {
+                opcode: Opcode::Alr,
+                mode: AddressingMode::Immediate,
+            },
+            /* Anc */
+            0x0B => Instruction {
+                opcode: Opcode::Anc,
+                mode: AddressingMode::Immediate,
+            },
+            0x2B => Instruction {
+                opcode: Opcode::Anc,
+                mode: AddressingMode::Immediate,
             },
             /* And */
             0x29 => Instruction {
                 opcode: Opcode::And,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0x25 => Instruction {
                 opcode: Opcode::And,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x35 => Instruction {
                 opcode: Opcode::And,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x2D => Instruction {
                 opcode: Opcode::And,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x3D => Instruction {
                 opcode: Opcode::And,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0x39 => Instruction {
                 opcode: Opcode::And,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteY,
             },
             0x21 => Instruction {
                 opcode: Opcode::And,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x31 => Instruction {
                 opcode: Opcode::And,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
+            },
+            /* Arr */
+            0x6B => Instruction {
+                opcode: Opcode::Arr,
+                mode: AddressingMode::Immediate,
             },
             /* Asl */
             0x0A => Instruction {
                 opcode: Opcode::Asl,
                 mode: AddressingMode::Accumulator,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0x06 => Instruction {
                 opcode: Opcode::Asl,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x16 => Instruction {
                 opcode: Opcode::Asl,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x0E => Instruction {
                 opcode: Opcode::Asl,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x1E => Instruction {
                 opcode: Opcode::Asl,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
+            },
+            /* Axs */
+            0xCB => Instruction {
+                opcode: Opcode::Axs,
+                mode: AddressingMode::Immediate,
             },
             /* Bcc */
             0x90 => Instruction {
                 opcode: Opcode::Bcc,
-                mode: AddressingMode::Relative(signed_byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: true,
+                mode: AddressingMode::Relative,
             },
             /* Bcs */
             0xB0 => Instruction {
                 opcode: Opcode::Bcs,
-                mode: AddressingMode::Relative(signed_byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: true,
+                mode: AddressingMode::Relative,
             },
             /* Beq */
             0xF0 => Instruction {
                 opcode: Opcode::Beq,
-                mode: AddressingMode::Relative(signed_byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: true,
+                mode: AddressingMode::Relative,
             },
             /* Bit */
             0x24 => Instruction {
                 opcode: Opcode::Bit,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x2C => Instruction {
                 opcode: Opcode::Bit,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             /* Bmi */
             0x30 => Instruction {
                 opcode: Opcode::Bmi,
-                mode: AddressingMode::Relative(signed_byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false, /* Is this right? */
+                mode: AddressingMode::Relative, /* Is this right? */
             },
             /* Bne */
             0xD0 => Instruction {
                 opcode: Opcode::Bne,
-                mode: AddressingMode::Relative(signed_byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: true,
+                mode: AddressingMode::Relative,
             },
             /* Bpl */
             0x10 => Instruction {
                 opcode: Opcode::Bpl,
-                mode: AddressingMode::Relative(signed_byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: true,
+                mode: AddressingMode::Relative,
             },
             /* Brk */
             0x00 => Instruction {
                 opcode: Opcode::Brk,
-                mode: AddressingMode::Implicit,
-                cycles: 0, // this generates an interrupt which adds the right number of cycles itself
-                page_cross_cost: false,
+                mode: AddressingMode::Implicit, // this generates an interrupt which adds the right number of cycles itself
             },
             /* Bvc */
             0x50 => Instruction {
                 opcode: Opcode::Bvc,
-                mode: AddressingMode::Relative(signed_byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: true,
+                mode: AddressingMode::Relative,
             },
             /* Bvs */
             0x70 => Instruction {
                 opcode: Opcode::Bvs,
-                mode: AddressingMode::Relative(signed_byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: true,
+                mode: AddressingMode::Relative,
             },
             /* Clc */
             0x18 => Instruction {
                 opcode: Opcode::Clc,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Cld */
             0xD8 => Instruction {
                 opcode: Opcode::Cld,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Cli */
             0x58 => Instruction {
                 opcode: Opcode::Cli,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Clv */
             0xB8 => Instruction {
                 opcode: Opcode::Clv,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Cmp */
             0xC9 => Instruction {
                 opcode: Opcode::Cmp,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xC5 => Instruction {
                 opcode: Opcode::Cmp,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xD5 => Instruction {
                 opcode: Opcode::Cmp,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xCD => Instruction {
                 opcode: Opcode::Cmp,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xDD => Instruction {
                 opcode: Opcode::Cmp,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0xD9 => Instruction {
                 opcode: Opcode::Cmp,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteY,
             },
             0xC1 => Instruction {
                 opcode: Opcode::Cmp,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0xD1 => Instruction {
                 opcode: Opcode::Cmp,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: true,
+                mode: AddressingMode::IndirectY,
             },
             /* Cpx */
             0xE0 => Instruction {
                 opcode: Opcode::Cpx,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xE4 => Instruction {
                 opcode: Opcode::Cpx,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xEC => Instruction {
                 opcode: Opcode::Cpx,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             /* Cpy */
             0xC0 => Instruction {
                 opcode: Opcode::Cpy,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xC4 => Instruction {
                 opcode: Opcode::Cpy,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xCC => Instruction {
                 opcode: Opcode::Cpy,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             /* Dcp */
             0xC3 => Instruction {
                 opcode: Opcode::Dcp,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0xC7 => Instruction {
                 opcode: Opcode::Dcp,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xCF => Instruction {
                 opcode: Opcode::Dcp,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xD3 => Instruction {
                 opcode: Opcode::Dcp,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
             },
             0xD7 => Instruction {
                 opcode: Opcode::Dcp,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xDB => Instruction {
                 opcode: Opcode::Dcp,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteY,
             },
             0xDF => Instruction {
                 opcode: Opcode::Dcp,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Dec */
             0xC6 => Instruction {
                 opcode: Opcode::Dec,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xD6 => Instruction {
                 opcode: Opcode::Dec,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xCE => Instruction {
                 opcode: Opcode::Dec,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xDE => Instruction {
                 opcode: Opcode::Dec,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Dex */
             0xCA => Instruction {
                 opcode: Opcode::Dex,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Dey */
             0x88 => Instruction {
                 opcode: Opcode::Dey,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Eor */
             0x49 => Instruction {
                 opcode: Opcode::Eor,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0x45 => Instruction {
                 opcode: Opcode::Eor,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x55 => Instruction {
                 opcode: Opcode::Eor,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x4D => Instruction {
                 opcode: Opcode::Eor,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x5D => Instruction {
                 opcode: Opcode::Eor,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0x59 => Instruction {
                 opcode: Opcode::Eor,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteY,
             },
             0x41 => Instruction {
                 opcode: Opcode::Eor,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x51 => Instruction {
                 opcode: Opcode::Eor,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: true,
+                mode: AddressingMode::IndirectY,
             },
             /* Inc */
             0xE6 => Instruction {
                 opcode: Opcode::Inc,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xF6 => Instruction {
                 opcode: Opcode::Inc,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xEE => Instruction {
                 opcode: Opcode::Inc,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xFE => Instruction {
                 opcode: Opcode::Inc,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Inx */
             0xE8 => Instruction {
                 opcode: Opcode::Inx,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Iny */
             0xC8 => Instruction {
                 opcode: Opcode::Iny,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Isc */
             0xE3 => Instruction {
                 opcode: Opcode::Isc,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0xE7 => Instruction {
                 opcode: Opcode::Isc,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xEF => Instruction {
                 opcode: Opcode::Isc,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xF3 => Instruction {
                 opcode: Opcode::Isc,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
             },
             0xF7 => Instruction {
                 opcode: Opcode::Isc,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xFB => Instruction {
                 opcode: Opcode::Isc,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteY,
             },
             0xFF => Instruction {
                 opcode: Opcode::Isc,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Jmp */
             0x4C => Instruction {
                 opcode: Opcode::Jmp,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x6C => Instruction {
                 opcode: Opcode::Jmp,
-                mode: AddressingMode::Indirect(word_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::Indirect,
             },
             /* Jsr */
             0x20 => Instruction {
                 opcode: Opcode::Jsr,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
+            },
+            /* Las */
+            0xBB => Instruction {
+                opcode: Opcode::Las,
+                mode: AddressingMode::AbsoluteY,
             },
             /* Lax */
             0xA3 => Instruction {
                 opcode: Opcode::Lax,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0xA7 => Instruction {
                 opcode: Opcode::Lax,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xAF => Instruction {
                 opcode: Opcode::Lax,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xB3 => Instruction {
                 opcode: Opcode::Lax,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: true,
+                mode: AddressingMode::IndirectY,
             },
             0xB7 => Instruction {
                 opcode: Opcode::Lax,
-                mode: AddressingMode::ZeroPageY(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageY,
             },
             0xBF => Instruction {
                 opcode: Opcode::Lax,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteY,
             },
             /* Lda */
             0xA9 => Instruction {
                 opcode: Opcode::Lda,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xA5 => Instruction {
                 opcode: Opcode::Lda,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xB5 => Instruction {
                 opcode: Opcode::Lda,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xAD => Instruction {
                 opcode: Opcode::Lda,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xBD => Instruction {
                 opcode: Opcode::Lda,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0xB9 => Instruction {
                 opcode: Opcode::Lda,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteY,
             },
             0xA1 => Instruction {
                 opcode: Opcode::Lda,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0xB1 => Instruction {
                 opcode: Opcode::Lda,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: true,
+                mode: AddressingMode::IndirectY,
             },
             /* Ldx */
             0xA2 => Instruction {
                 opcode: Opcode::Ldx,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xA6 => Instruction {
                 opcode: Opcode::Ldx,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xB6 => Instruction {
                 opcode: Opcode::Ldx,
-                mode: AddressingMode::ZeroPageY(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageY,
             },
             0xAE => Instruction {
                 opcode: Opcode::Ldx,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xBE => Instruction {
                 opcode: Opcode::Ldx,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteY,
             },
             /* Ldy */
             0xA0 => Instruction {
                 opcode: Opcode::Ldy,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xA4 => Instruction {
                 opcode: Opcode::Ldy,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xB4 => Instruction {
                 opcode: Opcode::Ldy,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xAC => Instruction {
                 opcode: Opcode::Ldy,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xBC => Instruction {
                 opcode: Opcode::Ldy,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Lsr */
             0x4A => Instruction {
                 opcode: Opcode::Lsr,
                 mode: AddressingMode::Accumulator,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0x46 => Instruction {
                 opcode: Opcode::Lsr,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x56 => Instruction {
                 opcode: Opcode::Lsr,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x4E => Instruction {
                 opcode: Opcode::Lsr,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x5E => Instruction {
                 opcode: Opcode::Lsr,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
+            },
+            /* Lxa */
+            0xAB => Instruction {
+                opcode: Opcode::Lxa,
+                mode: AddressingMode::Immediate,
             },
             /* Nop */
             0x04 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x0C => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x14 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x1A => Instruction {
                 opcode: Opcode::Nop,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0x1C => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0x3A => Instruction {
                 opcode: Opcode::Nop,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0x34 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x3C => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0x44 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x54 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x5A => Instruction {
                 opcode: Opcode::Nop,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0x5C => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0x64 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x74 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x7A => Instruction {
                 opcode: Opcode::Nop,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0x7C => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0x80 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0x82 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0x89 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xC2 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xD4 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xDA => Instruction {
                 opcode: Opcode::Nop,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0xDC => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0xE2 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0xEA => Instruction {
                 opcode: Opcode::Nop,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0xF4 => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xFA => Instruction {
                 opcode: Opcode::Nop,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0xFC => Instruction {
                 opcode: Opcode::Nop,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Ora */
             0x09 => Instruction {
                 opcode: Opcode::Ora,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0x05 => Instruction {
                 opcode: Opcode::Ora,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x15 => Instruction {
                 opcode: Opcode::Ora,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x0D => Instruction {
                 opcode: Opcode::Ora,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x1D => Instruction {
                 opcode: Opcode::Ora,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0x19 => Instruction {
                 opcode: Opcode::Ora,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteY,
             },
             0x01 => Instruction {
                 opcode: Opcode::Ora,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x11 => Instruction {
                 opcode: Opcode::Ora,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
             },
             /* Pha */
             0x48 => Instruction {
                 opcode: Opcode::Pha,
                 mode: AddressingMode::Implicit,
-                cycles: 3,
-                page_cross_cost: false,
             },
             /* Php */
             0x08 => Instruction {
                 opcode: Opcode::Php,
                 mode: AddressingMode::Implicit,
-                cycles: 3,
-                page_cross_cost: false,
             },
             /* Pla */
             0x68 => Instruction {
                 opcode: Opcode::Pla,
                 mode: AddressingMode::Implicit,
-                cycles: 4,
-                page_cross_cost: false,
             },
             /* Plp */
             0x28 => Instruction {
                 opcode: Opcode::Plp,
                 mode: AddressingMode::Implicit,
-                cycles: 4,
-                page_cross_cost: false,
             },
             /* Rla */
             0x23 => Instruction {
                 opcode: Opcode::Rla,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x27 => Instruction {
                 opcode: Opcode::Rla,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x2F => Instruction {
                 opcode: Opcode::Rla,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x33 => Instruction {
                 opcode: Opcode::Rla,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
             },
             0x37 => Instruction {
                 opcode: Opcode::Rla,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x3B => Instruction {
                 opcode: Opcode::Rla,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteY,
             },
             0x3F => Instruction {
                 opcode: Opcode::Rla,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Rol */
             0x2A => Instruction {
                 opcode: Opcode::Rol,
                 mode: AddressingMode::Accumulator,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0x26 => Instruction {
                 opcode: Opcode::Rol,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x36 => Instruction {
                 opcode: Opcode::Rol,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x2E => Instruction {
                 opcode: Opcode::Rol,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x3E => Instruction {
                 opcode: Opcode::Rol,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Ror */
             0x6A => Instruction {
                 opcode: Opcode::Ror,
                 mode: AddressingMode::Accumulator,
-                cycles: 2,
-                page_cross_cost: false,
             },
             0x66 => Instruction {
                 opcode: Opcode::Ror,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x76 => Instruction {
                 opcode: Opcode::Ror,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x6E => Instruction {
                 opcode: Opcode::Ror,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x7E => Instruction {
                 opcode: Opcode::Ror,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Rra */
             0x63 => Instruction {
                 opcode: Opcode::Rra,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x67 => Instruction {
                 opcode: Opcode::Rra,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x6F => Instruction {
                 opcode: Opcode::Rra,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x73 => Instruction {
                 opcode: Opcode::Rra,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
             },
             0x77 => Instruction {
                 opcode: Opcode::Rra,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x7B => Instruction {
                 opcode: Opcode::Rra,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteY,
             },
             0x7F => Instruction {
                 opcode: Opcode::Rra,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Rti */
             0x40 => Instruction {
                 opcode: Opcode::Rti,
                 mode: AddressingMode::Implicit,
-                cycles: 6,
-                page_cross_cost: false,
             },
             /* Rts */
             0x60 => Instruction {
                 opcode: Opcode::Rts,
                 mode: AddressingMode::Implicit,
-                cycles: 6,
-                page_cross_cost: false,
             },
             /* Sax */
             0x83 => Instruction {
                 opcode: Opcode::Sax,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x87 => Instruction {
                 opcode: Opcode::Sax,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x8F => Instruction {
                 opcode: Opcode::Sax,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x97 => Instruction {
                 opcode: Opcode::Sax,
-                mode: AddressingMode::ZeroPageY(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageY,
             },
             /* Sbc */
             0xE9 => Instruction {
                 opcode: Opcode::Sbc,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xEB => Instruction {
                 opcode: Opcode::Sbc,
-                mode: AddressingMode::Immediate(byte_after_opcode),
-                cycles: 2,
-                page_cross_cost: false,
+                mode: AddressingMode::Immediate,
             },
             0xE5 => Instruction {
                 opcode: Opcode::Sbc,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0xF5 => Instruction {
                 opcode: Opcode::Sbc,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0xED => Instruction {
                 opcode: Opcode::Sbc,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0xFD => Instruction {
                 opcode: Opcode::Sbc,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteX,
             },
             0xF9 => Instruction {
                 opcode: Opcode::Sbc,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: true,
+                mode: AddressingMode::AbsoluteY,
             },
             0xE1 => Instruction {
                 opcode: Opcode::Sbc,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0xF1 => Instruction {
                 opcode: Opcode::Sbc,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
             },
             /* Sec */
             0x38 => Instruction {
                 opcode: Opcode::Sec,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Sed */
             0xF8 => Instruction {
                 opcode: Opcode::Sed,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Sei */
             0x78 => Instruction {
                 opcode: Opcode::Sei,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
+            },
+            /* Sha */
+            0x9F => Instruction {
+                opcode: Opcode::Sha,
+                mode: AddressingMode::AbsoluteY,
+            },
+            0x93 => Instruction {
+                opcode: Opcode::Sha,
+                mode: AddressingMode::IndirectY,
+            },
+            /* Shx */
+            0x9E => Instruction {
+                opcode: Opcode::Shx,
+                mode: AddressingMode::AbsoluteY,
+            },
+            /* Shy */
+            0x9C => Instruction {
+                opcode: Opcode::Shy,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Slo */
             0x03 => Instruction {
                 opcode: Opcode::Slo,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x07 => Instruction {
                 opcode: Opcode::Slo,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x0F => Instruction {
                 opcode: Opcode::Slo,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x13 => Instruction {
                 opcode: Opcode::Slo,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
             },
             0x17 => Instruction {
                 opcode: Opcode::Slo,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x1B => Instruction {
                 opcode: Opcode::Slo,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteY,
             },
             0x1F => Instruction {
                 opcode: Opcode::Slo,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Sre */
             0x43 => Instruction {
                 opcode: Opcode::Sre,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x47 => Instruction {
                 opcode: Opcode::Sre,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x4F => Instruction {
                 opcode: Opcode::Sre,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x53 => Instruction {
                 opcode: Opcode::Sre,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 8,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
             },
             0x57 => Instruction {
                 opcode: Opcode::Sre,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x5B => Instruction {
                 opcode: Opcode::Sre,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteY,
             },
             0x5F => Instruction {
                 opcode: Opcode::Sre,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 7,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             /* Sta */
             0x85 => Instruction {
                 opcode: Opcode::Sta,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x95 => Instruction {
                 opcode: Opcode::Sta,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x8D => Instruction {
                 opcode: Opcode::Sta,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             0x9D => Instruction {
                 opcode: Opcode::Sta,
-                mode: AddressingMode::AbsoluteX(word_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteX,
             },
             0x99 => Instruction {
                 opcode: Opcode::Sta,
-                mode: AddressingMode::AbsoluteY(word_after_opcode),
-                cycles: 5,
-                page_cross_cost: false,
+                mode: AddressingMode::AbsoluteY,
             },
             0x81 => Instruction {
                 opcode: Opcode::Sta,
-                mode: AddressingMode::IndirectX(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectX,
             },
             0x91 => Instruction {
                 opcode: Opcode::Sta,
-                mode: AddressingMode::IndirectY(byte_after_opcode),
-                cycles: 6,
-                page_cross_cost: false,
+                mode: AddressingMode::IndirectY,
             },
             /* Stx */
             0x86 => Instruction {
                 opcode: Opcode::Stx,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x96 => Instruction {
                 opcode: Opcode::Stx,
-                mode: AddressingMode::ZeroPageY(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageY,
             },
             0x8E => Instruction {
                 opcode: Opcode::Stx,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
             },
             /* Sty */
             0x84 => Instruction {
                 opcode: Opcode::Sty,
-                mode: AddressingMode::ZeroPage(byte_after_opcode),
-                cycles: 3,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPage,
             },
             0x94 => Instruction {
                 opcode: Opcode::Sty,
-                mode: AddressingMode::ZeroPageX(byte_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::ZeroPageX,
             },
             0x8C => Instruction {
                 opcode: Opcode::Sty,
-                mode: AddressingMode::Absolute(word_after_opcode),
-                cycles: 4,
-                page_cross_cost: false,
+                mode: AddressingMode::Absolute,
+            },
+            /* Tas */
+            0x9B => Instruction {
+                opcode: Opcode::Tas,
+                mode: AddressingMode::AbsoluteY,
             },
             /* Tax */
             0xAA => Instruction {
                 opcode: Opcode::Tax,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Tay */
             0xA8 => Instruction {
                 opcode: Opcode::Tay,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Tsx */
             0xBA => Instruction {
                 opcode: Opcode::Tsx,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Txa */
             0x8A => Instruction {
                 opcode: Opcode::Txa,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Txs */
             0x9A => Instruction {
                 opcode: Opcode::Txs,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
             /* Tya */
             0x98 => Instruction {
                 opcode: Opcode::Tya,
                 mode: AddressingMode::Implicit,
-                cycles: 2,
-                page_cross_cost: false,
             },
-            opcode => panic!("0x{:02X} not implemented!", opcode),
+            /* Xaa */
+            0x8B => Instruction {
+                opcode: Opcode::Xaa,
+                mode: AddressingMode::Immediate,
+            },
+            /* Jam */
+            0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xB2 | 0xD2 | 0xF2 => {
+                // The CPU locks up after reading the byte after the opcode, and only a reset gets
+                // it going again
+                self.read(self.pc.wrapping_add(1));
+                self.jam_opcode = Some(opcode);
+                return Err(EmuError::CpuJam {
+                    opcode,
+                    pc: self.pc,
+                });
+            }
         };
 
-        let instruction_length = 1 + match result.mode {
-            AddressingMode::ZeroPage(_) => 1,
-            AddressingMode::ZeroPageX(_) => 1,
-            AddressingMode::ZeroPageY(_) => 1,
-            AddressingMode::IndirectX(_) => 1,
-            AddressingMode::IndirectY(_) => 1,
-            AddressingMode::Immediate(_) => 1,
-            AddressingMode::Relative(_) => 1,
-            AddressingMode::Absolute(_) => 2,
-            AddressingMode::AbsoluteX(_) => 2,
-            AddressingMode::AbsoluteY(_) => 2,
-            AddressingMode::Indirect(_) => 2,
-            AddressingMode::Implicit => 0,
-            AddressingMode::Accumulator => 0,
-        };
+        self.pc = self.pc.wrapping_add(1);
 
-        self.pc += instruction_length;
-        result
+        // Instructions without an operand still read the byte after the opcode, and ignore it
+        if matches!(
+            result.mode,
+            AddressingMode::Implicit | AddressingMode::Accumulator
+        ) {
+            self.read(self.pc);
+        }
+
+        Ok(result)
     }
 
-    pub fn execute_instruction(&mut self, instruction: Instruction) {
+    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), EmuError> {
         match instruction.opcode {
-            Opcode::Add => self.adc(instruction.mode),
-            Opcode::And => self.and(instruction.mode),
-            Opcode::Asl => self.asl(instruction.mode),
-            Opcode::Bcc => self.bcc(instruction.mode),
-            Opcode::Bcs => self.bcs(instruction.mode),
-            Opcode::Beq => self.beq(instruction.mode),
-            Opcode::Bit => self.bit(instruction.mode),
-            Opcode::Bmi => self.bmi(instruction.mode),
-            Opcode::Bne => self.bne(instruction.mode),
-            Opcode::Bpl => self.bpl(instruction.mode),
-            Opcode::Brk => self.brk(instruction.mode),
-            Opcode::Bvc => self.bvc(instruction.mode),
-            Opcode::Bvs => self.bvs(instruction.mode),
+            Opcode::Add => self.adc(instruction.mode)?,
+            Opcode::Alr => self.alr(instruction.mode)?,
+            Opcode::Anc => self.anc(instruction.mode)?,
+            Opcode::And => self.and(instruction.mode)?,
+            Opcode::Arr => self.arr(instruction.mode)?,
+            Opcode::Asl => self.asl(instruction.mode)?,
+            Opcode::Axs => self.axs(instruction.mode)?,
+            Opcode::Bcc => self.bcc(instruction.mode)?,
+            Opcode::Bcs => self.bcs(instruction.mode)?,
+            Opcode::Beq => self.beq(instruction.mode)?,
+            Opcode::Bit => self.bit(instruction.mode)?,
+            Opcode::Bmi => self.bmi(instruction.mode)?,
+            Opcode::Bne => self.bne(instruction.mode)?,
+            Opcode::Bpl => self.bpl(instruction.mode)?,
+            Opcode::Brk => self.brk(instruction.mode)?,
+            Opcode::Bvc => self.bvc(instruction.mode)?,
+            Opcode::Bvs => self.bvs(instruction.mode)?,
             Opcode::Clc => self.clc(),
             Opcode::Cld => self.cld(),
             Opcode::Cli => self.cli(),
             Opcode::Clv => self.clv(),
-            Opcode::Cmp => self.cmp(instruction.mode),
-            Opcode::Cpx => self.cpx(instruction.mode),
-            Opcode::Cpy => self.cpy(instruction.mode),
-            Opcode::Dcp => self.dcp(instruction.mode),
-            Opcode::Dec => self.dec(instruction.mode),
+            Opcode::Cmp => self.cmp(instruction.mode)?,
+            Opcode::Cpx => self.cpx(instruction.mode)?,
+            Opcode::Cpy => self.cpy(instruction.mode)?,
+            Opcode::Dcp => self.dcp(instruction.mode)?,
+            Opcode::Dec => self.dec(instruction.mode)?,
             Opcode::Dex => self.dex(),
             Opcode::Dey => self.dey(),
-            Opcode::Eor => self.eor(instruction.mode),
-            Opcode::Inc => self.inc(instruction.mode),
+            Opcode::Eor => self.eor(instruction.mode)?,
+            Opcode::Inc => self.inc(instruction.mode)?,
             Opcode::Inx => self.inx(),
             Opcode::Iny => self.iny(),
-            Opcode::Isc => self.isc(instruction.mode),
-            Opcode::Jmp => self.jmp(instruction.mode),
-            Opcode::Jsr => self.jsr(instruction.mode),
-            Opcode::Lax => self.lax(instruction.mode),
-            Opcode::Lda => self.lda(instruction.mode),
-            Opcode::Ldx => self.ldx(instruction.mode),
-            Opcode::Ldy => self.ldy(instruction.mode),
-            Opcode::Lsr => self.lsr(instruction.mode),
-            Opcode::Nop => self.nop(instruction.mode),
-            Opcode::Ora => self.ora(instruction.mode),
+            Opcode::Isc => self.isc(instruction.mode)?,
+            Opcode::Jmp => self.jmp(instruction.mode)?,
+            Opcode::Jsr => self.jsr(instruction.mode)?,
+            Opcode::Las => self.las(instruction.mode)?,
+            Opcode::Lax => self.lax(instruction.mode)?,
+            Opcode::Lda => self.lda(instruction.mode)?,
+            Opcode::Ldx => self.ldx(instruction.mode)?,
+            Opcode::Ldy => self.ldy(instruction.mode)?,
+            Opcode::Lsr => self.lsr(instruction.mode)?,
+            Opcode::Lxa => self.lxa(instruction.mode)?,
+            Opcode::Nop => self.nop(instruction.mode)?,
+            Opcode::Ora => self.ora(instruction.mode)?,
             Opcode::Pha => self.pha(),
             Opcode::Php => self.php(),
             Opcode::Pla => self.pla(),
             Opcode::Plp => self.plp(),
-            Opcode::Rla => self.rla(instruction.mode),
-            Opcode::Rol => self.rol(instruction.mode),
-            Opcode::Ror => self.ror(instruction.mode),
-            Opcode::Rra => self.rra(instruction.mode),
+            Opcode::Rla => self.rla(instruction.mode)?,
+            Opcode::Rol => self.rol(instruction.mode)?,
+            Opcode::Ror => self.ror(instruction.mode)?,
+            Opcode::Rra => self.rra(instruction.mode)?,
             Opcode::Rti => self.rti(),
             Opcode::Rts => self.rts(),
-            Opcode::Sax => self.sax(instruction.mode),
-            Opcode::Sbc => self.sbc(instruction.mode),
+            Opcode::Sax => self.sax(instruction.mode)?,
+            Opcode::Sbc => self.sbc(instruction.mode)?,
             Opcode::Sec => self.sec(),
             Opcode::Sed => self.sed(),
             Opcode::Sei => self.sei(),
-            Opcode::Slo => self.slo(instruction.mode),
-            Opcode::Sre => self.sre(instruction.mode),
-            Opcode::Sta => self.sta(instruction.mode),
-            Opcode::Stx => self.stx(instruction.mode),
-            Opcode::Sty => self.sty(instruction.mode),
+            Opcode::Sha => self.sha(instruction.mode)?,
+            Opcode::Shx => self.shx(instruction.mode)?,
+            Opcode::Shy => self.shy(instruction.mode)?,
+            Opcode::Slo => self.slo(instruction.mode)?,
+            Opcode::Sre => self.sre(instruction.mode)?,
+            Opcode::Sta => self.sta(instruction.mode)?,
+            Opcode::Stx => self.stx(instruction.mode)?,
+            Opcode::Sty => self.sty(instruction.mode)?,
+            Opcode::Tas => self.tas(instruction.mode)?,
             Opcode::Tax => self.tax(),
             Opcode::Tay => self.tay(),
             Opcode::Tsx => self.tsx(),
             Opcode::Txa => self.txa(),
             Opcode::Txs => self.txs(),
             Opcode::Tya => self.tya(),
+            Opcode::Xaa => self.xaa(instruction.mode)?,
         }
+
+        Ok(())
     }
 
-    fn read_with_addressing_mode(&mut self, mode: AddressingMode) -> u8 {
-        match mode {
-            AddressingMode::ZeroPage(val) => self.bus.get_byte_at(u16::from(val)),
-            AddressingMode::ZeroPageX(val) => {
-                self.bus.get_byte_at(u16::from(val.wrapping_add(self.x)))
+    fn fetch_operand_byte(&mut self) -> u8 {
+        let val = self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        val
+    }
+
+    fn fetch_operand_word(&mut self) -> u16 {
+        let low_byte = self.fetch_operand_byte();
+        let high_byte = self.fetch_operand_byte();
+
+        ((high_byte as u16) << 8) | low_byte as u16
+    }
+
+    // Add an index to a 16-bit base address. The CPU adds to the low byte first and reads from
+    // the result before it has carried into the high byte; that read is only repeated at the right
+    // address when the addition crossed a page, but writes always wait for the high byte.
+    fn index_address(&mut self, base: u16, index: u8, access: Access) -> u16 {
+        let addr = base.wrapping_add(u16::from(index));
+
+        if access == Access::Write || (addr & 0xFF00) != (base & 0xFF00) {
+            self.read((base & 0xFF00) | (addr & 0x00FF));
+        }
+
+        addr
+    }
+
+    // Fetch an instruction's operand and work out the address it refers to, making the same bus
+    // accesses the CPU does along the way.
+    fn operand_address(&mut self, mode: AddressingMode, access: Access) -> Result<u16, EmuError> {
+        let addr = match mode {
+            AddressingMode::Immediate => {
+                let addr = self.pc;
+                self.pc = self.pc.wrapping_add(1);
+                addr
             }
-            AddressingMode::ZeroPageY(val) => {
-                self.bus.get_byte_at(u16::from(val.wrapping_add(self.y)))
+            AddressingMode::ZeroPage => u16::from(self.fetch_operand_byte()),
+            // Zero page indexing reads the unindexed address while it adds, and never leaves page 0
+            AddressingMode::ZeroPageX => {
+                let base = self.fetch_operand_byte();
+                self.read(u16::from(base));
+                u16::from(base.wrapping_add(self.x))
             }
-            AddressingMode::Absolute(addr) => self.bus.get_byte_at(addr),
-            AddressingMode::AbsoluteX(val) => self.bus.get_byte_at(val + u16::from(self.x)),
-            AddressingMode::AbsoluteY(val) => {
-                self.bus.get_byte_at(val.wrapping_add(u16::from(self.y)))
+            AddressingMode::ZeroPageY => {
+                let base = self.fetch_operand_byte();
+                self.read(u16::from(base));
+                u16::from(base.wrapping_add(self.y))
             }
-            AddressingMode::IndirectX(val) => {
-                let low_byte = self.bus.get_byte_at((val.wrapping_add(self.x)) as u16);
-                let high_byte = self
-                    .bus
-                    .get_byte_at((val.wrapping_add(self.x).wrapping_add(1)) as u16);
-                let indirect_addr = ((high_byte as u16) << 8) | (low_byte as u16);
-                self.bus.get_byte_at(indirect_addr)
+            AddressingMode::Absolute => self.fetch_operand_word(),
+            AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::IndirectY => {
+                let (base, index) = self.fetch_indexed_base(mode)?;
+                self.index_address(base, index, access)
             }
-            AddressingMode::IndirectY(val) => {
-                let low_byte = self.bus.get_byte_at(val as u16);
-                let high_byte = self.bus.get_byte_at((val.wrapping_add(1)) as u16);
-                let indirect_addr = ((high_byte as u16) << 8) | (low_byte as u16);
-                self.bus
-                    .get_byte_at(indirect_addr.wrapping_add(u16::from(self.y)))
+            AddressingMode::IndirectX => {
+                let pointer = self.fetch_operand_byte();
+                self.read(u16::from(pointer));
+                let pointer = pointer.wrapping_add(self.x);
+                let low_byte = self.read(u16::from(pointer));
+                let high_byte = self.read(u16::from(pointer.wrapping_add(1)));
+                ((high_byte as u16) << 8) | (low_byte as u16)
             }
-            AddressingMode::Immediate(val) => val,
-            AddressingMode::Accumulator => self.accumulator,
-            _ => unreachable!(
-                "Attempted to read from address value of {:?} illegally",
-                mode
-            ),
-        }
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
+        };
+
+        Ok(addr)
     }
 
-    fn write_with_addressing_mode(&mut self, mode: AddressingMode, assigned_val: u8) {
-        match mode {
-            AddressingMode::ZeroPage(val) => self.bus.set_byte_at(u16::from(val), assigned_val),
-            AddressingMode::ZeroPageX(val) => self
-                .bus
-                .set_byte_at(u16::from(val.wrapping_add(self.x)), assigned_val),
-            AddressingMode::ZeroPageY(val) => self
-                .bus
-                .set_byte_at(u16::from(val.wrapping_add(self.y)), assigned_val),
-            AddressingMode::Absolute(addr) => self.bus.set_byte_at(addr, assigned_val),
-            AddressingMode::AbsoluteX(val) => {
-                self.bus.set_byte_at(val + u16::from(self.x), assigned_val)
-            }
-            AddressingMode::AbsoluteY(val) => {
-                self.bus.set_byte_at(val + u16::from(self.y), assigned_val)
-            }
-            AddressingMode::IndirectX(val) => {
-                let low_byte = self.bus.get_byte_at((val.wrapping_add(self.x)) as u16);
-                let high_byte = self
-                    .bus
-                    .get_byte_at((val.wrapping_add(self.x).wrapping_add(1)) as u16);
-                let indirect_addr = ((high_byte as u16) << 8) | (low_byte as u16);
-                self.bus.set_byte_at(indirect_addr, assigned_val);
-            }
-            AddressingMode::IndirectY(val) => {
-                let low_byte = self.bus.get_byte_at(val as u16);
-                let high_byte = self.bus.get_byte_at((val.wrapping_add(1)) as u16);
-                let indirect_addr =
-                    (((high_byte as u16) << 8) | (low_byte as u16)).wrapping_add(self.y as u16);
-                self.bus.set_byte_at(indirect_addr, assigned_val);
+    // Fetch the operand of an indexed addressing mode (other than zero page), returning the base
+    // address and the index to add to it.
+    fn fetch_indexed_base(&mut self, mode: AddressingMode) -> Result<(u16, u8), EmuError> {
+        let res = match mode {
+            AddressingMode::AbsoluteX => (self.fetch_operand_word(), self.x),
+            AddressingMode::AbsoluteY => (self.fetch_operand_word(), self.y),
+            AddressingMode::IndirectY => {
+                let pointer = self.fetch_operand_byte();
+                let low_byte = self.read(u16::from(pointer));
+                let high_byte = self.read(u16::from(pointer.wrapping_add(1)));
+                (((high_byte as u16) << 8) | (low_byte as u16), self.y)
             }
-            AddressingMode::Accumulator => self.accumulator = assigned_val,
-            _ => panic!(
-                "Attempted to write to address value of {:?} illegally",
-                mode
-            ),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
+        };
+
+        Ok(res)
+    }
+
+    // SHA, SHX, SHY and TAS store a value ANDed with one more than the high byte of the base
+    // address. When the index crosses a page, the stored value also replaces the high byte of the
+    // address written to.
+    fn store_and_high_byte(&mut self, mode: AddressingMode, val: u8) -> Result<(), EmuError> {
+        let (base, index) = self.fetch_indexed_base(mode)?;
+        let mut addr = self.index_address(base, index, Access::Write);
+        let result = val & ((base >> 8) as u8).wrapping_add(1);
+
+        if (addr & 0xFF00) != (base & 0xFF00) {
+            addr = (u16::from(result) << 8) | (addr & 0x00FF);
         }
+        self.write(addr, result);
+
+        Ok(())
     }
 
-    fn read_crosses_page_boundry(&mut self, mode: AddressingMode) -> bool {
-        match mode {
-            AddressingMode::AbsoluteX(val) => {
-                let (_, overflow) = (val as u8).overflowing_add(self.x);
-                overflow
-            }
-            AddressingMode::AbsoluteY(val) => {
-                let (_, overflow) = (val as u8).overflowing_add(self.y);
-                overflow
-            }
-            AddressingMode::IndirectY(val) => {
-                let (_, overflow) = self.bus.get_byte_at(val as u16).overflowing_add(self.y);
-                overflow
-            }
-            _ => false,
+    fn read_with_addressing_mode(&mut self, mode: AddressingMode) -> Result<u8, EmuError> {
+        let addr = self.operand_address(mode, Access::Read)?;
+
+        Ok(self.read(addr))
+    }
+
+    fn write_with_addressing_mode(
+        &mut self,
+        mode: AddressingMode,
+        assigned_val: u8,
+    ) -> Result<(), EmuError> {
+        let addr = self.operand_address(mode, Access::Write)?;
+        self.write(addr, assigned_val);
+
+        Ok(())
+    }
+
+    // Read-modify-write instructions read the old value, write it straight back while they work
+    // on it, and then write the new value. Returns the new value.
+    fn read_modify_write(
+        &mut self,
+        mode: AddressingMode,
+        modify: fn(&mut Self, u8) -> u8,
+    ) -> Result<u8, EmuError> {
+        if mode == AddressingMode::Accumulator {
+            self.accumulator = modify(self, self.accumulator);
+            return Ok(self.accumulator);
         }
+
+        let addr = self.operand_address(mode, Access::Write)?;
+        let old_val = self.read(addr);
+        self.write(addr, old_val);
+        let new_val = modify(self, old_val);
+        self.write(addr, new_val);
+
+        Ok(new_val)
+    }
+
+    fn adc(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let to_be_added = self.read_with_addressing_mode(mode)?;
+        self.add_with_carry(to_be_added);
+
+        Ok(())
     }
 
-    fn adc(&mut self, mode: AddressingMode) {
-        let to_be_added = self.read_with_addressing_mode(mode);
+    fn add_with_carry(&mut self, to_be_added: u8) {
         let old_accumulator = self.accumulator;
 
         let (first_add, first_carry) = old_accumulator.overflowing_add(to_be_added);
@@ -1885,106 +1814,184 @@ impl Cpu {
         self.carry = first_carry | second_carry;
     }
 
-    fn and(&mut self, mode: AddressingMode) {
-        let to_be_anded = self.read_with_addressing_mode(mode);
+    // Equivalent to and then lsr on the accumulator
+    fn alr(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let to_be_anded = self.read_with_addressing_mode(mode)?;
+        self.accumulator = self.shift_right(self.accumulator & to_be_anded);
+
+        Ok(())
+    }
+
+    // Equivalent to and, but also copies bit 7 of the result into carry
+    fn anc(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.and(mode)?;
+        self.carry = self.sign;
+
+        Ok(())
+    }
+
+    fn and(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let to_be_anded = self.read_with_addressing_mode(mode)?;
         let result = to_be_anded & self.accumulator;
 
         self.accumulator = result;
         self.sign = (result as i8) < 0;
         self.zero = result == 0;
+
+        Ok(())
     }
 
-    fn asl(&mut self, mode: AddressingMode) {
-        let to_be_asled = self.read_with_addressing_mode(mode);
+    // Equivalent to and then ror on the accumulator, except carry and overflow are set from bits 6
+    // and 5 of the result
+    fn arr(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let to_be_anded = self.read_with_addressing_mode(mode)?;
+        let result = ((self.accumulator & to_be_anded) >> 1) | ((self.carry as u8) << 7);
+
+        self.accumulator = result;
+        self.sign = (result as i8) < 0;
+        self.zero = result == 0;
+        self.carry = (result & (1 << 6)) != 0;
+        self.overflow = (((result >> 6) ^ (result >> 5)) & 0x1) != 0;
+
+        Ok(())
+    }
+
+    fn asl(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.read_modify_write(mode, Self::shift_left)?;
+
+        Ok(())
+    }
+
+    fn shift_left(&mut self, to_be_asled: u8) -> u8 {
         let result = to_be_asled << 1;
 
-        self.write_with_addressing_mode(mode, result);
         self.sign = (result as i8) < 0;
         self.zero = result == 0;
         self.carry = (to_be_asled & (1 << 7)) != 0;
+
+        result
+    }
+
+    // Subtracts from (A & X) without borrow into X, setting flags like cmp
+    fn axs(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let to_subtract = self.read_with_addressing_mode(mode)?;
+        let anded = self.accumulator & self.x;
+
+        self.compare(anded, to_subtract);
+        self.x = anded.wrapping_sub(to_subtract);
+
+        Ok(())
     }
 
-    fn branch(&mut self, condition: bool, offset: i8) {
+    fn branch(&mut self, condition: bool) {
+        let offset = self.fetch_operand_byte() as i8;
+
         if condition {
-            // If branch is taken, add 1 cycle
-            self.cycles_left += 1;
+            // A taken branch spends a cycle adding the offset. An IRQ that shows up during that
+            // cycle has to wait for the next instruction, unless the branch crosses a page.
+            if self.irq_detected && !self.irq_pending {
+                self.irq_detected = false;
+            }
+            self.read(self.pc);
             let new_pc = (self.pc as i32 + offset as i32) as u16;
 
-            // If branch is to new page, add 1 more cycle
+            // If branch is to new page, it takes 1 more cycle to fix up the high byte
             if (new_pc >> 8) != (self.pc >> 8) {
-                self.cycles_left += 1;
+                self.read((self.pc & 0xFF00) | (new_pc & 0x00FF));
             }
 
             self.pc = new_pc;
         }
     }
 
-    fn bcc(&mut self, mode: AddressingMode) {
+    fn bcc(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Relative(offset) => self.branch(!self.carry, offset),
-            _ => panic!("Cannot branch using {:?}", mode),
+            AddressingMode::Relative => self.branch(!self.carry),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
     }
 
-    fn bcs(&mut self, mode: AddressingMode) {
+    fn bcs(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Relative(offset) => self.branch(self.carry, offset),
-            _ => panic!("Cannot branch using {:?}", mode),
+            AddressingMode::Relative => self.branch(self.carry),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
     }
 
-    fn beq(&mut self, mode: AddressingMode) {
+    fn beq(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Relative(offset) => self.branch(self.zero, offset),
-            _ => panic!("Cannot branch using {:?}", mode),
+            AddressingMode::Relative => self.branch(self.zero),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
     }
 
-    fn bit(&mut self, mode: AddressingMode) {
-        let val = self.read_with_addressing_mode(mode);
+    fn bit(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let val = self.read_with_addressing_mode(mode)?;
         self.sign = (val & (1 << 7)) != 0;
         self.overflow = (val & (1 << 6)) != 0;
 
         self.zero = (val & self.accumulator) == 0;
+
+        Ok(())
     }
 
-    fn bmi(&mut self, mode: AddressingMode) {
+    fn bmi(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Relative(offset) => self.branch(self.sign, offset),
-            _ => panic!("Cannot branch using {:?}", mode),
+            AddressingMode::Relative => self.branch(self.sign),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
     }
 
-    fn bne(&mut self, mode: AddressingMode) {
+    fn bne(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Relative(offset) => self.branch(!self.zero, offset),
-            _ => panic!("Cannot branch using {:?}", mode),
+            AddressingMode::Relative => self.branch(!self.zero),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
     }
 
-    fn bpl(&mut self, mode: AddressingMode) {
+    fn bpl(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Relative(offset) => self.branch(!self.sign, offset),
-            _ => panic!("Cannot branch using {:?}", mode),
+            AddressingMode::Relative => self.branch(!self.sign),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
     }
 
-    fn brk(&mut self, _mode: AddressingMode) {
-        self.interrupt(Interrupt::Irq);
+    fn brk(&mut self, _mode: AddressingMode) -> Result<(), EmuError> {
+        // BRK skips the byte after it, so the return address is 2 past the opcode
+        self.pc = self.pc.wrapping_add(1);
+        self.interrupt(Interrupt::Brk);
+
+        Ok(())
     }
 
-    fn bvc(&mut self, mode: AddressingMode) {
+    fn bvc(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Relative(offset) => self.branch(!self.overflow, offset),
-            _ => panic!("Cannot branch using {:?}", mode),
+            AddressingMode::Relative => self.branch(!self.overflow),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
     }
 
-    fn bvs(&mut self, mode: AddressingMode) {
+    fn bvs(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Relative(offset) => self.branch(self.overflow, offset),
-            _ => panic!("Cannot branch using {:?}", mode),
+            AddressingMode::Relative => self.branch(self.overflow),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
     }
 
     fn clc(&mut self) {
@@ -2003,43 +2010,54 @@ impl Cpu {
         self.overflow = false;
     }
 
-    fn cmp(&mut self, mode: AddressingMode) {
-        let to_compare = self.read_with_addressing_mode(mode);
+    fn cmp(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let to_compare = self.read_with_addressing_mode(mode)?;
+        self.compare(self.accumulator, to_compare);
 
-        self.sign = (self.accumulator.wrapping_sub(to_compare) as i8) < 0;
-        self.zero = self.accumulator == to_compare;
-        self.carry = self.accumulator >= to_compare;
+        Ok(())
     }
 
-    fn cpx(&mut self, mode: AddressingMode) {
-        let to_compare = self.read_with_addressing_mode(mode);
+    fn compare(&mut self, register: u8, to_compare: u8) {
+        self.sign = (register.wrapping_sub(to_compare) as i8) < 0;
+        self.zero = register == to_compare;
+        self.carry = register >= to_compare;
+    }
+
+    fn cpx(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let to_compare = self.read_with_addressing_mode(mode)?;
+        self.compare(self.x, to_compare);
 
-        self.sign = (self.x.wrapping_sub(to_compare) as i8) < 0;
-        self.zero = self.x == to_compare;
-        self.carry = self.x >= to_compare;
+        Ok(())
     }
 
-    fn cpy(&mut self, mode: AddressingMode) {
-        let to_compare = self.read_with_addressing_mode(mode);
+    fn cpy(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let to_compare = self.read_with_addressing_mode(mode)?;
+        self.compare(self.y, to_compare);
 
-        self.sign = (self.y.wrapping_sub(to_compare) as i8) < 0;
-        self.zero = self.y == to_compare;
-        self.carry = self.y >= to_compare;
+        Ok(())
     }
 
     // Equivalent to dec then cmp
-    fn dcp(&mut self, mode: AddressingMode) {
-        self.dec(mode);
-        self.cmp(mode);
+    fn dcp(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let result = self.read_modify_write(mode, Self::decrement)?;
+        self.compare(self.accumulator, result);
+
+        Ok(())
+    }
+
+    fn dec(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.read_modify_write(mode, Self::decrement)?;
+
+        Ok(())
     }
 
-    fn dec(&mut self, mode: AddressingMode) {
-        let old_val = self.read_with_addressing_mode(mode);
+    fn decrement(&mut self, old_val: u8) -> u8 {
         let result = old_val.wrapping_sub(1);
-        self.write_with_addressing_mode(mode, result);
 
         self.sign = (result as i8) < 0;
         self.zero = result == 0;
+
+        result
     }
 
     fn dex(&mut self) {
@@ -2056,20 +2074,28 @@ impl Cpu {
         self.zero = self.y == 0;
     }
 
-    fn eor(&mut self, mode: AddressingMode) {
-        self.accumulator ^= self.read_with_addressing_mode(mode);
+    fn eor(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.accumulator ^= self.read_with_addressing_mode(mode)?;
 
         self.sign = (self.accumulator as i8) < 0;
         self.zero = self.accumulator == 0;
+
+        Ok(())
+    }
+
+    fn inc(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.read_modify_write(mode, Self::increment)?;
+
+        Ok(())
     }
 
-    fn inc(&mut self, mode: AddressingMode) {
-        let old_val = self.read_with_addressing_mode(mode);
+    fn increment(&mut self, old_val: u8) -> u8 {
         let result = old_val.wrapping_add(1);
-        self.write_with_addressing_mode(mode, result);
 
         self.sign = (result as i8) < 0;
         self.zero = result == 0;
+
+        result
     }
 
     fn inx(&mut self) {
@@ -2087,97 +2113,148 @@ impl Cpu {
     }
 
     // Equivalent to inc then sbc
-    fn isc(&mut self, mode: AddressingMode) {
-        self.inc(mode);
-        self.sbc(mode);
+    fn isc(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let result = self.read_modify_write(mode, Self::increment)?;
+        self.add_with_carry(!result);
+
+        Ok(())
     }
 
-    fn jmp(&mut self, mode: AddressingMode) {
+    fn jmp(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Absolute(addr) => {
-                self.pc = addr;
+            AddressingMode::Absolute => {
+                self.pc = self.fetch_operand_word();
             }
             // jmp (xxFF) will read from xxFF and xx00 instead of crossing page boundary.
-            AddressingMode::Indirect(addr) => {
-                if addr & 0x00FF == 0xFF {
-                    let low_byte = self.bus.get_byte_at(addr);
-                    let high_byte = self.bus.get_byte_at(addr & 0xFF00);
-                    self.pc = ((high_byte as u16) << 8) | (low_byte as u16);
-                } else {
-                    self.pc = self.bus.get_word_at(addr);
-                }
+            AddressingMode::Indirect => {
+                let addr = self.fetch_operand_word();
+                let low_byte = self.read(addr);
+                let high_byte = self.read((addr & 0xFF00) | (addr.wrapping_add(1) & 0x00FF));
+                self.pc = ((high_byte as u16) << 8) | (low_byte as u16);
             }
-            _ => panic!("Cannot jmp using {:?}", mode),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
     }
 
-    fn jsr(&mut self, mode: AddressingMode) {
+    fn jsr(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         match mode {
-            AddressingMode::Absolute(addr) => {
-                self.push_word(self.pc - 1);
-                self.pc = addr;
+            // The high byte of the target is only read after the return address (which points at
+            // it) has been pushed
+            AddressingMode::Absolute => {
+                let low_byte = self.fetch_operand_byte();
+                self.dummy_stack_read();
+                self.push_word(self.pc);
+                let high_byte = self.read(self.pc);
+                self.pc = ((high_byte as u16) << 8) | (low_byte as u16);
             }
-            _ => panic!("Cannot jsr using {:?}", mode),
+            _ => return Err(EmuError::InvalidAddressingMode(mode)),
         }
+
+        Ok(())
+    }
+
+    // Loads A, X and the stack pointer with memory ANDed with the stack pointer
+    fn las(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let result = self.read_with_addressing_mode(mode)? & self.sp;
+
+        self.accumulator = result;
+        self.x = result;
+        self.sp = result;
+        self.sign = (result as i8) < 0;
+        self.zero = result == 0;
+
+        Ok(())
     }
 
     // Shortcut for lda then tax
-    fn lax(&mut self, mode: AddressingMode) {
+    fn lax(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         // lda
-        self.accumulator = self.read_with_addressing_mode(mode);
+        self.accumulator = self.read_with_addressing_mode(mode)?;
 
         self.sign = (self.accumulator as i8) < 0;
         self.zero = self.accumulator == 0;
 
         // tax
         self.x = self.accumulator;
+
+        Ok(())
     }
 
-    fn lda(&mut self, mode: AddressingMode) {
-        self.accumulator = self.read_with_addressing_mode(mode);
+    fn lda(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.accumulator = self.read_with_addressing_mode(mode)?;
 
         self.sign = (self.accumulator as i8) < 0;
         self.zero = self.accumulator == 0;
+
+        Ok(())
     }
 
-    fn ldx(&mut self, mode: AddressingMode) {
-        self.x = self.read_with_addressing_mode(mode);
+    fn ldx(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.x = self.read_with_addressing_mode(mode)?;
 
         self.sign = (self.x as i8) < 0;
         self.zero = self.x == 0;
+
+        Ok(())
     }
 
-    fn ldy(&mut self, mode: AddressingMode) {
-        self.y = self.read_with_addressing_mode(mode);
+    fn ldy(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.y = self.read_with_addressing_mode(mode)?;
 
         self.sign = (self.y as i8) < 0;
         self.zero = self.y == 0;
+
+        Ok(())
+    }
+
+    fn lsr(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.read_modify_write(mode, Self::shift_right)?;
+
+        Ok(())
     }
 
-    fn lsr(&mut self, mode: AddressingMode) {
-        let to_be_lsred = self.read_with_addressing_mode(mode);
+    fn shift_right(&mut self, to_be_lsred: u8) -> u8 {
         let result = to_be_lsred >> 1;
 
-        self.write_with_addressing_mode(mode, result);
         self.sign = false;
         self.zero = result == 0;
         self.carry = (to_be_lsred & (1 << 0)) != 0;
+
+        result
     }
 
-    fn nop(&mut self, mode: AddressingMode) {
-        // If mode isn't implicit, actually do the read. Some nop instructions may use addressing
-        // modes with "oops" cycles, so we have to actually do the read no matter what to account
-        // for this.
+    // Shortcut for lda #imm then tax. On a real 6502 the operand is ANDed with A ORed with a
+    // constant that varies between chips; on NES consoles the constant is usually $FF, which leaves
+    // just the operand.
+    fn lxa(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.accumulator = self.read_with_addressing_mode(mode)?;
+        self.x = self.accumulator;
+
+        self.sign = (self.accumulator as i8) < 0;
+        self.zero = self.accumulator == 0;
+
+        Ok(())
+    }
+
+    fn nop(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        // If mode isn't implicit, actually do the read. Some nop instructions use addressing modes
+        // with page crossing cycles, and the reads still happen (and can have side effects).
         if mode != AddressingMode::Implicit {
-            self.read_with_addressing_mode(mode);
+            self.read_with_addressing_mode(mode)?;
         }
+
+        Ok(())
     }
 
-    fn ora(&mut self, mode: AddressingMode) {
-        self.accumulator |= self.read_with_addressing_mode(mode);
+    fn ora(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.accumulator |= self.read_with_addressing_mode(mode)?;
 
         self.sign = (self.accumulator as i8) < 0;
         self.zero = self.accumulator == 0;
+
+        Ok(())
     }
 
     fn pha(&mut self) {
@@ -2185,18 +2262,11 @@ impl Cpu {
     }
 
     fn php(&mut self) {
-        let processor_status: u8 = ((self.sign as u8) << 7)
-            | ((self.overflow as u8) << 6)
-            | ((1 as u8) << 5)
-            | ((1 as u8) << 4)
-            | ((self.decimal as u8) << 3)
-            | ((self.interrupt as u8) << 2)
-            | ((self.zero as u8) << 1)
-            | (self.carry as u8);
-        self.push_byte(processor_status);
+        self.push_byte(self.status());
     }
 
     fn pla(&mut self) {
+        self.dummy_stack_read();
         self.accumulator = self.pop_byte();
 
         self.zero = self.accumulator == 0;
@@ -2204,6 +2274,11 @@ impl Cpu {
     }
 
     fn plp(&mut self) {
+        self.dummy_stack_read();
+        self.pull_status();
+    }
+
+    fn pull_status(&mut self) {
         let processor_flags = self.pop_byte();
 
         self.sign = (processor_flags & (1 << 7)) != 0;
@@ -2217,72 +2292,92 @@ impl Cpu {
     }
 
     // Equivalent to rol then and
-    fn rla(&mut self, mode: AddressingMode) {
-        self.rol(mode);
-        self.and(mode);
+    fn rla(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let result = self.read_modify_write(mode, Self::rotate_left)?;
+
+        self.accumulator &= result;
+        self.sign = (self.accumulator as i8) < 0;
+        self.zero = self.accumulator == 0;
+
+        Ok(())
+    }
+
+    fn rol(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.read_modify_write(mode, Self::rotate_left)?;
+
+        Ok(())
     }
 
-    fn rol(&mut self, mode: AddressingMode) {
-        let to_be_roled = self.read_with_addressing_mode(mode);
+    fn rotate_left(&mut self, to_be_roled: u8) -> u8 {
         let new_val = (to_be_roled << 1) | (self.carry as u8);
 
-        self.write_with_addressing_mode(mode, new_val);
         self.sign = (new_val as i8) < 0;
         self.zero = new_val == 0;
         self.carry = (to_be_roled & (1 << 7)) != 0;
+
+        new_val
+    }
+
+    fn ror(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.read_modify_write(mode, Self::rotate_right)?;
+
+        Ok(())
     }
 
-    fn ror(&mut self, mode: AddressingMode) {
-        let to_be_rored = self.read_with_addressing_mode(mode);
+    fn rotate_right(&mut self, to_be_rored: u8) -> u8 {
         let new_val = (to_be_rored >> 1) | ((self.carry as u8) << 7);
 
-        self.write_with_addressing_mode(mode, new_val);
         self.sign = (new_val as i8) < 0;
         self.zero = new_val == 0;
         self.carry = (to_be_rored & (1 << 0)) != 0;
+
+        new_val
     }
 
     // Equivalent to ror then adc
-    fn rra(&mut self, mode: AddressingMode) {
-        self.ror(mode);
-        self.adc(mode);
+    fn rra(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let result = self.read_modify_write(mode, Self::rotate_right)?;
+        self.add_with_carry(result);
+
+        Ok(())
     }
 
     fn rti(&mut self) {
         // Pull processor flags from stack
-        self.plp();
+        self.dummy_stack_read();
+        self.pull_status();
 
         self.pc = self.pop_word();
     }
 
     fn rts(&mut self) {
-        self.pc = self.pop_word() + 1;
+        self.dummy_stack_read();
+        self.pc = self.pop_word();
+
+        // The return address points at the last byte of the jsr, so it takes a cycle to step past
+        self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    fn sax(&mut self, mode: AddressingMode) {
+    fn sax(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         let result = self.accumulator & self.x;
 
-        self.write_with_addressing_mode(mode, result);
+        self.write_with_addressing_mode(mode, result)?;
+
+        Ok(())
     }
 
-    fn sbc(&mut self, mode: AddressingMode) {
+    fn sbc(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
         // We can take advantage of:
         // A - M - (1 - C)
         // A + !M + 1 - (1 - C)
         // A + !M + 1 + C - 1
         // A + !M + C -> same as adc
 
-        let to_be_added = !self.read_with_addressing_mode(mode);
-        let old_accumulator = self.accumulator;
+        let to_be_added = !self.read_with_addressing_mode(mode)?;
+        self.add_with_carry(to_be_added);
 
-        let (first_add, first_carry) = old_accumulator.overflowing_add(to_be_added);
-        let (result, second_carry) = first_add.overflowing_add(u8::from(self.carry));
-
-        self.accumulator = result;
-        self.sign = (result as i8) < 0;
-        self.zero = result == 0;
-        self.overflow = ((to_be_added ^ result) & (old_accumulator ^ result) & 0x80) != 0;
-        self.carry = first_carry | second_carry;
+        Ok(())
     }
 
     fn sec(&mut self) {
@@ -2297,28 +2392,62 @@ impl Cpu {
         self.interrupt = true;
     }
 
+    fn sha(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.store_and_high_byte(mode, self.accumulator & self.x)
+    }
+
+    fn shx(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.store_and_high_byte(mode, self.x)
+    }
+
+    fn shy(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.store_and_high_byte(mode, self.y)
+    }
+
     // Equivalent to asl then ora
-    fn slo(&mut self, mode: AddressingMode) {
-        self.asl(mode);
-        self.ora(mode);
+    fn slo(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let result = self.read_modify_write(mode, Self::shift_left)?;
+
+        self.accumulator |= result;
+        self.sign = (self.accumulator as i8) < 0;
+        self.zero = self.accumulator == 0;
+
+        Ok(())
     }
 
     // Equivalent to lsr then eor
-    fn sre(&mut self, mode: AddressingMode) {
-        self.lsr(mode);
-        self.eor(mode);
+    fn sre(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let result = self.read_modify_write(mode, Self::shift_right)?;
+
+        self.accumulator ^= result;
+        self.sign = (self.accumulator as i8) < 0;
+        self.zero = self.accumulator == 0;
+
+        Ok(())
     }
 
-    fn sta(&mut self, mode: AddressingMode) {
-        self.write_with_addressing_mode(mode, self.accumulator);
+    fn sta(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.write_with_addressing_mode(mode, self.accumulator)?;
+
+        Ok(())
     }
 
-    fn stx(&mut self, mode: AddressingMode) {
-        self.write_with_addressing_mode(mode, self.x);
+    fn stx(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.write_with_addressing_mode(mode, self.x)?;
+
+        Ok(())
     }
 
-    fn sty(&mut self, mode: AddressingMode) {
-        self.write_with_addressing_mode(mode, self.y);
+    fn sty(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.write_with_addressing_mode(mode, self.y)?;
+
+        Ok(())
+    }
+
+    // Sets the stack pointer to A & X, then stores it like sha
+    fn tas(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        self.sp = self.accumulator & self.x;
+        self.store_and_high_byte(mode, self.sp)
     }
 
     fn tax(&mut self) {
@@ -2359,4 +2488,16 @@ impl Cpu {
         self.zero = self.accumulator == 0;
         self.sign = (self.accumulator as i8) < 0;
     }
+
+    // A = (A | magic) & X & operand. The magic constant is unstable on real hardware; $EE is what
+    // it usually is.
+    fn xaa(&mut self, mode: AddressingMode) -> Result<(), EmuError> {
+        let operand = self.read_with_addressing_mode(mode)?;
+        self.accumulator = (self.accumulator | 0xEE) & self.x & operand;
+
+        self.sign = (self.accumulator as i8) < 0;
+        self.zero = self.accumulator == 0;
+
+        Ok(())
+    }
 }
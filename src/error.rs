@@ -0,0 +1,57 @@
+use crate::cpu::AddressingMode;
+
+use std::fmt;
+
+// Everything that can stop emulation. None of these are recoverable by the console itself, but a
+// frontend can report them and let the user inspect state, reset, or load a savestate.
+#[derive(Debug)]
+pub enum EmuError {
+    // The CPU executed one of the opcodes that lock up a real 6502
+    CpuJam { opcode: u8, pc: u16 },
+    // An instruction was decoded with an addressing mode it can't use
+    InvalidAddressingMode(AddressingMode),
+    // The cartridge uses a mapper we don't emulate
+    UnsupportedMapper(u16),
+    // The ROM file is malformed
+    InvalidRom(String),
+    // The patch file is malformed, or made for a different ROM
+    InvalidPatch(String),
+    // A ROM database override file is malformed
+    InvalidRomDatabase(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmuError::CpuJam { opcode, pc } => write!(
+                f,
+                "CPU jammed executing opcode 0x{:02X} at 0x{:04X}",
+                opcode, pc
+            ),
+            EmuError::InvalidAddressingMode(mode) => {
+                write!(f, "instruction cannot use addressing mode {:?}", mode)
+            }
+            EmuError::UnsupportedMapper(mapper) => write!(f, "mapper {} is not supported", mapper),
+            EmuError::InvalidRom(reason) => write!(f, "invalid ROM: {}", reason),
+            EmuError::InvalidPatch(reason) => write!(f, "invalid patch: {}", reason),
+            EmuError::InvalidRomDatabase(reason) => write!(f, "invalid ROM database: {}", reason),
+            EmuError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for EmuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmuError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for EmuError {
+    fn from(err: std::io::Error) -> Self {
+        EmuError::Io(err)
+    }
+}
@@ -0,0 +1,98 @@
+// Interfaces between the emulated console and whatever is showing its output and providing its
+// input. The console calls these; frontends implement them.
+
+#[cfg(feature = "sdl")]
+pub mod sdl;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Receives every finished frame.
+pub trait VideoSink {
+    // framebuffer holds one palette index (into ppu::PALETTE) per pixel, row by row.
+    fn present_frame(&mut self, framebuffer: &[u8]);
+}
+
+// Receives the audio produced while emulating each frame.
+pub trait AudioSink {
+    // Rate, in Hz, that samples passed to queue_samples should be produced at: the rate the
+    // device actually plays at, which may not be the one asked for. It's read once, when the
+    // console is built.
+    fn sample_rate(&self) -> u32;
+
+    fn queue_samples(&mut self, samples: &[f32]);
+
+    // How much faster than sample_rate the sink would like samples to be produced, to keep its
+    // buffer from running dry or filling up. Kept within a fraction of a percent of 1.0 so the
+    // change in pitch can't be heard.
+    fn rate_adjustment(&self) -> f64 {
+        1.0
+    }
+}
+
+// Provides the state of the buttons on the controllers.
+pub trait InputSource {
+    // Buttons held on the controller plugged into the given port (0 or 1), in the order the
+    // standard controller reports them: bit 0 is A, then B, Select, Start, Up, Down, Left, and bit
+    // 7 is Right.
+    fn buttons(&mut self, port: usize) -> u8;
+}
+
+// Discards every frame.
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn present_frame(&mut self, _framebuffer: &[u8]) {}
+}
+
+// Discards all audio.
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn sample_rate(&self) -> u32 {
+        44_100
+    }
+
+    fn queue_samples(&mut self, _samples: &[f32]) {}
+}
+
+// A controller with no buttons held.
+pub struct NullInput;
+
+impl InputSource for NullInput {
+    fn buttons(&mut self, _port: usize) -> u8 {
+        0
+    }
+}
+
+// Keeps frames on schedule by sleeping until each one is due. Deadlines are absolute, so a late
+// frame doesn't push back every frame after it.
+pub struct FramePacer {
+    frame_duration: Duration,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    // Any further behind than this and we give up on catching up, instead of running a burst of
+    // frames as fast as possible.
+    const MAX_LAG_FRAMES: u32 = 5;
+
+    pub fn new(frames_per_second: f64) -> Self {
+        Self {
+            frame_duration: Duration::from_secs_f64(1.0 / frames_per_second),
+            next_frame: Instant::now(),
+        }
+    }
+
+    // Block until the next frame is due.
+    pub fn wait(&mut self) {
+        self.next_frame += self.frame_duration;
+
+        let now = Instant::now();
+        if self.next_frame > now {
+            thread::sleep(self.next_frame - now);
+        } else if now - self.next_frame > self.frame_duration * Self::MAX_LAG_FRAMES {
+            self.next_frame = now;
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use sdl2::audio::{AudioQueue, AudioSpecDesired, AudioStatus};
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use crate::frontend::{AudioSink, InputSource, VideoSink};
+use crate::ppu;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+// Draws frames into a window, scaled up by an integer factor. With vsync enabled, presenting a
+// frame blocks until the display's next vertical blank.
+pub struct SdlVideo {
+    canvas: Canvas<Window>,
+    texture: Texture,
+    frame_pixels: Vec<u8>,
+}
+
+impl SdlVideo {
+    pub fn new(
+        video_subsystem: &sdl2::VideoSubsystem,
+        scale: u32,
+        vsync: bool,
+    ) -> Result<Self, String> {
+        let window = video_subsystem
+            .window(
+                "NES Terminal Window",
+                ppu::SCREEN_WIDTH as u32 * scale,
+                ppu::SCREEN_HEIGHT as u32 * scale,
+            )
+            .position_centered()
+            .build()
+            .map_err(|err| err.to_string())?;
+
+        let mut canvas_builder = window.into_canvas();
+        if vsync {
+            canvas_builder = canvas_builder.present_vsync();
+        }
+        let canvas = canvas_builder.build().map_err(|err| err.to_string())?;
+
+        let texture = canvas
+            .texture_creator()
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                ppu::SCREEN_WIDTH as u32,
+                ppu::SCREEN_HEIGHT as u32,
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            canvas,
+            texture,
+            frame_pixels: vec![0u8; ppu::SCREEN_WIDTH * ppu::SCREEN_HEIGHT * 3],
+        })
+    }
+}
+
+impl VideoSink for SdlVideo {
+    fn present_frame(&mut self, framebuffer: &[u8]) {
+        for (pixel, &palette_idx) in self.frame_pixels.chunks_mut(3).zip(framebuffer) {
+            let color = ppu::PALETTE[usize::from(palette_idx)];
+            pixel.copy_from_slice(&[color.r, color.g, color.b]);
+        }
+
+        let res = self
+            .texture
+            .update(None, &self.frame_pixels, ppu::SCREEN_WIDTH * 3)
+            .map_err(|err| err.to_string())
+            .and_then(|_| self.canvas.copy(&self.texture, None, None));
+        if let Err(err) = res {
+            eprintln!("Could not draw frame: {}", err);
+        }
+
+        self.canvas.present();
+    }
+}
+
+// Plays mono samples through an SDL audio queue, aiming to keep TARGET_LATENCY of audio queued.
+// Playback only starts once that much has built up, and starts over the same way if the queue
+// runs dry, so a stall costs one gap instead of constant crackling. With sync enabled, queueing
+// blocks until the queue is back down to the target, which paces emulation by the audio device's
+// clock. Audio that's fallen hopelessly far behind, as it does when running unthrottled, is dropped.
+pub struct SdlAudio {
+    queue: AudioQueue<f32>,
+    target_samples: u32,
+    sync: bool,
+}
+
+impl SdlAudio {
+    const TARGET_LATENCY: Duration = Duration::from_millis(50);
+    const SYNC_POLL_INTERVAL: Duration = Duration::from_millis(1);
+    // Furthest the rate adjustment goes from 1.0, when the queue is empty or twice the target
+    const MAX_RATE_DEVIATION: f64 = 0.005;
+    // How many times the target the queue can grow to before it's dropped
+    const MAX_QUEUED_TARGETS: u32 = 4;
+
+    pub fn new(
+        audio_subsystem: &sdl2::AudioSubsystem,
+        sample_rate: u32,
+        sync: bool,
+    ) -> Result<Self, String> {
+        let desired_spec = AudioSpecDesired {
+            freq: Some(sample_rate as i32),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let queue = audio_subsystem.open_queue::<f32, _>(None, &desired_spec)?;
+        let target_samples =
+            (f64::from(queue.spec().freq) * Self::TARGET_LATENCY.as_secs_f64()) as u32;
+
+        Ok(Self {
+            queue,
+            target_samples,
+            sync,
+        })
+    }
+
+    fn queued_samples(&self) -> u32 {
+        self.queue.size() / std::mem::size_of::<f32>() as u32
+    }
+}
+
+impl AudioSink for SdlAudio {
+    fn sample_rate(&self) -> u32 {
+        self.queue.spec().freq as u32
+    }
+
+    fn queue_samples(&mut self, samples: &[f32]) {
+        let queued = self.queued_samples();
+        if queued > self.target_samples * Self::MAX_QUEUED_TARGETS {
+            self.queue.clear();
+            self.queue.pause();
+        } else if queued == 0 {
+            self.queue.pause();
+        }
+
+        if !self.queue.queue(samples) {
+            eprintln!("Could not queue audio: {}", sdl2::get_error());
+            return;
+        }
+
+        if self.queue.status() != AudioStatus::Playing {
+            if self.queued_samples() >= self.target_samples {
+                self.queue.resume();
+            }
+        } else if self.sync {
+            while self.queued_samples() > self.target_samples {
+                thread::sleep(Self::SYNC_POLL_INTERVAL);
+            }
+        }
+    }
+
+    // Scales linearly with how far the queue is from the target.
+    fn rate_adjustment(&self) -> f64 {
+        let fill = f64::from(self.queued_samples()) / f64::from(self.target_samples);
+        1.0 + Self::MAX_RATE_DEVIATION * (1.0 - fill).clamp(-1.0, 1.0)
+    }
+}
+
+// Reads controller 1 from the keyboard. The event pump is shared with the frontend, which keeps
+// polling it for window events.
+pub struct SdlInput {
+    events: Rc<RefCell<sdl2::EventPump>>,
+}
+
+impl SdlInput {
+    pub fn new(events: Rc<RefCell<sdl2::EventPump>>) -> Self {
+        Self { events }
+    }
+}
+
+impl InputSource for SdlInput {
+    fn buttons(&mut self, port: usize) -> u8 {
+        // Only controller 1 is mapped to the keyboard
+        if port != 0 {
+            return 0;
+        }
+
+        let events = self.events.borrow();
+        let keyboard = events.keyboard_state();
+
+        [
+            Keycode::Z,      // A
+            Keycode::X,      // B
+            Keycode::RShift, // SELECT
+            Keycode::Return, // START
+            Keycode::Up,     // UP
+            Keycode::Down,   // DOWN
+            Keycode::Left,   // LEFT
+            Keycode::Right,  // RIGHT
+        ]
+        .iter()
+        .enumerate()
+        .filter(|&(_, &keycode)| {
+            keyboard.is_scancode_pressed(Scancode::from_keycode(keycode).unwrap())
+        })
+        .fold(0, |buttons, (i, _)| buttons | (1 << i))
+    }
+}
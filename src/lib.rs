@@ -0,0 +1,17 @@
+pub mod apu;
+pub mod bus;
+pub mod checksum;
+pub mod controller;
+pub mod cpu;
+pub mod error;
+pub mod frontend;
+pub mod mapper;
+pub mod movie;
+pub mod nes;
+pub mod patch;
+pub mod ppu;
+pub mod rom;
+pub mod savestate;
+
+pub use crate::error::EmuError;
+pub use crate::nes::Nes;
@@ -1,74 +1,331 @@
-mod bus;
-mod controller;
-mod cpu;
-mod ppu;
-mod rom;
-
-use crate::bus::Bus;
-use crate::controller::Controller;
-use crate::cpu::{Cpu, Interrupt};
-use crate::ppu::Ppu;
-use crate::rom::Rom;
-
-use std::cell::RefCell;
+mod options;
+
+use emulator::frontend::{NullAudio, NullInput, NullVideo};
+use emulator::movie::Movie;
+use emulator::rom::database::RomDatabase;
+use emulator::rom::Rom;
+use emulator::Nes;
+
+use crate::options::{Command, Options};
+
 use std::error::Error;
-use std::rc::Rc;
-use std::time::{Duration, Instant};
+use std::fs;
+use std::io::ErrorKind;
+use std::process;
+
+// How often battery-backed RAM is written to the save file while playing, so a crash loses at
+// most this many frames of progress
+const SAVE_FILE_FLUSH_FRAMES: u64 = 300;
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", options::USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, options::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(&options) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let rom = Rom::new("roms/galaga.nes")?;
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let patch_path = match &options.patch_path {
+        Some(path) => Some(path.clone()),
+        None if options.find_patch => Rom::find_patch(&options.rom_path),
+        None => None,
+    };
 
-    let sdl_context = sdl2::init()?;
+    let mut rom = match &patch_path {
+        Some(path) => {
+            println!("Applying patch {}", path);
+            Rom::new_patched(&options.rom_path, path)
+        }
+        None => Rom::new(&options.rom_path),
+    }
+    .map_err(|err| format!("could not load ROM '{}': {}", options.rom_path, err))?;
 
-    let sdl_video_subsystem = sdl_context.video()?;
+    if let Some(path) = &options.rom_db_path {
+        let database = RomDatabase::load(path)
+            .map_err(|err| format!("could not load ROM database '{}': {}", path, err))?;
+        database.apply(&mut rom);
+    }
 
-    let sdl_events = Rc::new(RefCell::new(sdl_context.event_pump()?));
+    for warning in &rom.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    for correction in &rom.corrections {
+        println!("Corrected ROM header from the database: {}", correction);
+    }
 
-    let ppu = Ppu::new(&sdl_video_subsystem);
+    let movie = match &options.movie_path {
+        Some(path) => Some(
+            Movie::new(path).map_err(|err| format!("could not load movie '{}': {}", path, err))?,
+        ),
+        None => None,
+    };
 
-    let controller = Controller::new(Rc::clone(&sdl_events));
+    if options.headless {
+        run_headless(options, rom, movie)
+    } else {
+        sdl_frontend::run(options, rom, movie)
+    }
+}
 
-    let bus = Bus::new(rom, ppu, controller);
-    let mut cpu = Cpu::new(bus);
+// Set up playback, the game's save file and the starting savestate requested on the command line.
+fn prepare(
+    nes: &mut Nes,
+    options: &Options,
+    movie: Option<Movie>,
+) -> Result<SaveFile, Box<dyn Error>> {
+    if let Some(movie) = movie {
+        nes.set_movie(movie);
+    }
 
-    cpu.interrupt(Interrupt::Reset);
+    let save_file = SaveFile::open(nes, options.save_file_path())?;
 
-    let mut master_clock_ticks: u64 = 0;
-    let mut last_frame_start = 0;
+    if let Some(path) = &options.state_path {
+        fs::read(path)
+            .and_then(|data| nes.load_state(&data))
+            .map_err(|err| format!("could not load savestate '{}': {}", path, err))?;
+    }
 
-    let time_per_cpu_cycle = Duration::new(1, 0) / (236_250_000 / 11 / 12);
-    let mut cpu_cycle_start_time = Instant::now();
-    let _start_time = Instant::now();
+    Ok(save_file)
+}
 
-    // Master clocks run at 21.477272 MHz
-    loop {
-        // CPU runs every 12 master ticks ;)
-        if master_clock_ticks % 12 == 0 {
-            cpu.step();
+fn report_movie_finished(nes: &mut Nes) {
+    if let Some(frames) = nes.take_movie_finished() {
+        println!("Movie playback finished after {} frames", frames);
+    }
+}
 
-            while cpu_cycle_start_time.elapsed() < time_per_cpu_cycle {} // spinlock :/
+// Keeps the cartridge's battery-backed RAM in a file while the emulator isn't running, the way the
+// battery keeps it while the console is off.
+struct SaveFile {
+    path: String,
+    saved: Option<Vec<u8>>, // what the file has, so it's only written when the RAM changes
+}
 
-            cpu_cycle_start_time = Instant::now();
+impl SaveFile {
+    // Load the save file into battery-backed RAM, if the game has any and the file exists.
+    fn open(nes: &mut Nes, path: String) -> Result<Self, Box<dyn Error>> {
+        if nes.battery_ram().is_some() {
+            match fs::read(&path) {
+                Ok(data) => {
+                    if !nes.load_battery_ram(&data) {
+                        eprintln!(
+                            "warning: save file '{}' is the wrong size for this game",
+                            path
+                        );
+                    }
+                    println!("Loaded save file {}", path);
+                }
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(format!("could not load save file '{}': {}", path, err).into())
+                }
+            }
         }
 
-        // PPU runs every 4 master ticks
-        if master_clock_ticks % 4 == 0 {
-            let new_frame = cpu.bus.ppu.step();
-            if new_frame {
-                let current_steps = master_clock_ticks / 4;
-                let _last_frame_steps = current_steps - last_frame_start;
-                last_frame_start = current_steps;
+        Ok(Self {
+            saved: nes.battery_ram(),
+            path,
+        })
+    }
+
+    // Write battery-backed RAM to the save file if it's changed since it was last written.
+    fn flush(&mut self, nes: &Nes) {
+        if let Some(ram) = nes.battery_ram() {
+            if self.saved.as_ref() != Some(&ram) {
+                match fs::write(&self.path, &ram) {
+                    Ok(()) => self.saved = Some(ram),
+                    Err(err) => eprintln!("Could not write save file: {}", err),
+                }
             }
         }
+    }
+}
+
+// Without a window there is nothing to pace against, so frames are emulated as fast as possible.
+fn run_headless(options: &Options, rom: Rom, movie: Option<Movie>) -> Result<(), Box<dyn Error>> {
+    let region = options.region.unwrap_or_else(|| rom.region());
+    let mut nes = Nes::new(
+        rom,
+        region,
+        Box::new(NullVideo),
+        Box::new(NullAudio),
+        Box::new(NullInput),
+    )?;
+    let mut save_file = prepare(&mut nes, options, movie)?;
+
+    let res = loop {
+        if let Err(err) = nes.run_frame() {
+            break Err(format!("{}\n{}", err, nes.cpu.trace()).into());
+        }
+        report_movie_finished(&mut nes);
+
+        if nes.frame_count() % SAVE_FILE_FLUSH_FRAMES == 0 {
+            save_file.flush(&nes);
+        }
+
+        if options.frame_limit == Some(nes.frame_count()) {
+            break Ok(());
+        }
+    };
+
+    save_file.flush(&nes);
+    res
+}
+
+#[cfg(feature = "sdl")]
+mod sdl_frontend {
+    use emulator::frontend::sdl::{SdlAudio, SdlInput, SdlVideo};
+    use emulator::frontend::{AudioSink, FramePacer, NullAudio};
+    use emulator::movie::Movie;
+    use emulator::rom::Rom;
+    use emulator::Nes;
+
+    use crate::options::{Options, Pacing};
+
+    use sdl2::event::Event;
+    use sdl2::keyboard::Keycode;
+
+    use std::cell::RefCell;
+    use std::error::Error;
+    use std::fs;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+
+    // How often events are polled while emulation is paused
+    const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+    pub fn run(options: &Options, rom: Rom, movie: Option<Movie>) -> Result<(), Box<dyn Error>> {
+        let sdl_context = sdl2::init()?;
+
+        let sdl_events = Rc::new(RefCell::new(sdl_context.event_pump()?));
+
+        let video = SdlVideo::new(
+            &sdl_context.video()?,
+            options.scale,
+            options.pacing == Pacing::Vsync,
+        )?;
+
+        // A missing audio device shouldn't stop anyone from playing, though it leaves nothing for
+        // audio sync to wait on
+        let mut pacing = options.pacing;
+        let audio: Box<dyn AudioSink> = match sdl_context
+            .audio()
+            .and_then(|audio| SdlAudio::new(&audio, options.sample_rate, pacing == Pacing::Audio))
+        {
+            Ok(audio) => Box::new(audio),
+            Err(err) => {
+                eprintln!("warning: could not open audio device: {}", err);
+                if pacing == Pacing::Audio {
+                    pacing = Pacing::Timer;
+                }
+                Box::new(NullAudio)
+            }
+        };
+
+        let input = SdlInput::new(Rc::clone(&sdl_events));
+
+        let region = options.region.unwrap_or_else(|| rom.region());
+        let mut nes = Nes::new(rom, region, Box::new(video), audio, Box::new(input))?;
+        let mut save_file = super::prepare(&mut nes, options, movie)?;
+
+        let quicksave_path = options.quicksave_path();
+
+        let mut pacer = match pacing {
+            Pacing::Timer => Some(FramePacer::new(region.frames_per_second())),
+            Pacing::Vsync | Pacing::Unthrottled | Pacing::Audio => None,
+        };
+
+        // Set when emulation fails, so the user can still look at the last frame and reset or load
+        // a savestate instead of losing the session.
+        let mut paused = false;
+
+        loop {
+            if !paused {
+                if let Err(err) = nes.run_frame() {
+                    eprintln!("error: {}\n{}", err, nes.cpu.trace());
+                    eprintln!("Emulation paused. Press R to reset or F7 to load a state.");
+                    paused = true;
+                }
+                super::report_movie_finished(&mut nes);
+            }
+
+            if nes.frame_count() % super::SAVE_FILE_FLUSH_FRAMES == 0 {
+                save_file.flush(&nes);
+            }
+
+            if options.frame_limit == Some(nes.frame_count()) {
+                break;
+            }
 
-        if master_clock_ticks % 10000 == 0 {
             for event in sdl_events.borrow_mut().poll_iter() {
-                if let sdl2::event::Event::Quit { .. } = event {
-                    panic!("Exiting!");
+                match event {
+                    Event::Quit { .. } => {
+                        save_file.flush(&nes);
+                        return Ok(());
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F5),
+                        ..
+                    } => match fs::write(&quicksave_path, nes.save_state()) {
+                        Ok(()) => println!("Saved state to {}", quicksave_path),
+                        Err(err) => eprintln!("Could not save state: {}", err),
+                    },
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F7),
+                        ..
+                    } => match fs::read(&quicksave_path).and_then(|data| nes.load_state(&data)) {
+                        Ok(()) => {
+                            println!("Loaded state from {}", quicksave_path);
+                            paused = false;
+                        }
+                        Err(err) => eprintln!("Could not load state: {}", err),
+                    },
+                    Event::KeyDown {
+                        keycode: Some(Keycode::R),
+                        ..
+                    } if paused => {
+                        nes.reset();
+                        paused = false;
+                    }
+                    _ => {}
                 }
             }
+
+            if paused {
+                thread::sleep(PAUSED_POLL_INTERVAL);
+            } else if let Some(pacer) = &mut pacer {
+                pacer.wait();
+            }
         }
 
-        master_clock_ticks += 1;
+        save_file.flush(&nes);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "sdl"))]
+mod sdl_frontend {
+    use emulator::movie::Movie;
+    use emulator::rom::Rom;
+
+    use crate::options::Options;
+
+    use std::error::Error;
+
+    pub fn run(_options: &Options, _rom: Rom, _movie: Option<Movie>) -> Result<(), Box<dyn Error>> {
+        Err("this build has no SDL support, so only --headless is available".into())
     }
 }
@@ -0,0 +1,200 @@
+// Cartridge hardware. Everything the CPU sees at $4020-$FFFF and the PPU sees at $0000-$1FFF
+// comes from the cartridge, and the mapper on the cartridge decides what's actually there.
+
+mod axrom;
+mod cnrom;
+mod mmc1;
+mod mmc3;
+mod nrom;
+mod uxrom;
+
+use crate::error::EmuError;
+use crate::rom::{MirroringType, Rom};
+use crate::savestate::{StateReader, StateWriter};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub trait Mapper {
+    // Read from cartridge space ($4020-$FFFF). None means nothing drives the data bus, and the
+    // read returns open bus.
+    fn cpu_read(&mut self, addr: u16) -> Option<u8>;
+
+    fn cpu_write(&mut self, addr: u16, val: u8);
+
+    // Read from the pattern tables ($0000-$1FFF).
+    fn ppu_read(&mut self, addr: u16) -> u8;
+
+    fn ppu_write(&mut self, addr: u16, val: u8);
+
+    // How the PPU's 2KB of nametable RAM is laid out over $2000-$2FFF.
+    fn mirroring(&self) -> MirroringType;
+
+    // Whether the cartridge is currently pulling the CPU's IRQ line low.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // Called once per CPU cycle, for mappers that count cycles.
+    fn cpu_tick(&mut self) {}
+
+    // Called with every address the PPU puts on its address bus, for mappers that watch it (e.g.
+    // the MMC3 counting rises of A12).
+    fn ppu_address(&mut self, _addr: u16) {}
+
+    fn power_cycle(&mut self) {}
+
+    fn reset(&mut self) {}
+
+    // Battery-backed RAM on the cartridge, which frontends keep in a save file while the console
+    // is off. None if the cartridge has no battery.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+
+    fn save_state(&self, w: &mut StateWriter);
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error>;
+}
+
+// RAM on the cartridge at $6000-$7FFF, mirrored if it's smaller than 8KB. Copiers loaded a game's
+// trainer into $7000-$71FF of it, so it's put there at power on.
+pub struct PrgRam {
+    pub data: Vec<u8>,
+    battery_backed: bool,
+    trainer: Option<Vec<u8>>,
+}
+
+impl PrgRam {
+    // As much RAM as the ROM's header asks for, or 8KB if it has a trainer and asks for less, as
+    // that's what copiers had.
+    pub fn new(rom: &Rom) -> Self {
+        let mut size = rom.prg_ram_size + rom.prg_nvram_size;
+        if rom.trainer.is_some() {
+            size = std::cmp::max(size, 0x2000);
+        }
+
+        let mut res = Self {
+            data: vec![0u8; size],
+            battery_backed: rom.battery_backed_ram || rom.prg_nvram_size > 0,
+            trainer: rom.trainer.clone(),
+        };
+
+        res.power_cycle();
+
+        res
+    }
+
+    pub fn power_cycle(&mut self) {
+        // Battery-backed RAM keeps its contents while the console is off
+        if !self.battery_backed {
+            self.data.iter_mut().for_each(|byte| *byte = 0);
+        }
+        if let Some(trainer) = &self.trainer {
+            if self.data.len() >= 0x1200 {
+                self.data[0x1000..0x1200].copy_from_slice(trainer);
+            }
+        }
+    }
+
+    pub fn battery_data(&self) -> Option<&[u8]> {
+        if self.battery_backed && !self.data.is_empty() {
+            Some(&self.data)
+        } else {
+            None
+        }
+    }
+
+    pub fn battery_data_mut(&mut self) -> Option<&mut [u8]> {
+        if self.battery_backed && !self.data.is_empty() {
+            Some(&mut self.data)
+        } else {
+            None
+        }
+    }
+
+    // None if the cartridge has no PRG RAM.
+    pub fn read(&self, addr: u16) -> Option<u8> {
+        if self.data.is_empty() {
+            None
+        } else {
+            Some(self.data[usize::from(addr - 0x6000) % self.data.len()])
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) {
+        if !self.data.is_empty() {
+            let len = self.data.len();
+            self.data[usize::from(addr - 0x6000) % len] = val;
+        }
+    }
+}
+
+// Build the mapper the ROM asks for, with the ROM's contents loaded into it. For the discrete
+// mappers, NES 2.0 submapper 1 means a board without bus conflicts and 2 one with them; otherwise
+// they're emulated on the boards that usually have them (UNROM and CNROM) but not on AxROM, where
+// AOROM boards don't and some of its games rely on that. MMC3 submapper 4 is the NEC-made MMC3A,
+// with the older IRQ behaviour.
+pub fn new(rom: Rom) -> Result<Rc<RefCell<dyn Mapper>>, EmuError> {
+    // The mappers index their ROMs in whole banks, so refuse ROMs too small for that (CHR ROM only
+    // if there is any, since CHR RAM is always a full 8KB). MMC3 has two fixed 8KB PRG banks.
+    let (min_prg, min_chr) = match rom.mapper_number {
+        1 => (0x4000, 0x1000),
+        2 => (0x4000, 1),
+        3 => (1, 0x2000),
+        4 => (0x4000, 0x400),
+        _ => (1, 1),
+    };
+    if rom.prg_rom.len() < min_prg {
+        return Err(EmuError::InvalidRom(format!(
+            "{} bytes of PRG ROM is too little for mapper {}",
+            rom.prg_rom.len(),
+            rom.mapper_number
+        )));
+    }
+    if !rom.chr_rom.is_empty() && rom.chr_rom.len() < min_chr {
+        return Err(EmuError::InvalidRom(format!(
+            "{} bytes of CHR ROM is too little for mapper {}",
+            rom.chr_rom.len(),
+            rom.mapper_number
+        )));
+    }
+
+    match rom.mapper_number {
+        0 => Ok(Rc::new(RefCell::new(nrom::Nrom::new(rom)))),
+        1 => Ok(Rc::new(RefCell::new(mmc1::Mmc1::new(rom)))),
+        2 => {
+            let bus_conflicts = bus_conflicts(&rom, true);
+            Ok(Rc::new(RefCell::new(uxrom::Uxrom::new(rom, bus_conflicts))))
+        }
+        3 => {
+            let bus_conflicts = bus_conflicts(&rom, true);
+            Ok(Rc::new(RefCell::new(cnrom::Cnrom::new(rom, bus_conflicts))))
+        }
+        4 => {
+            let irq_variant = match rom.submapper_number {
+                4 => mmc3::IrqVariant::Nec,
+                _ => mmc3::IrqVariant::Sharp,
+            };
+            Ok(Rc::new(RefCell::new(mmc3::Mmc3::new(rom, irq_variant))))
+        }
+        7 => {
+            let bus_conflicts = bus_conflicts(&rom, false);
+            Ok(Rc::new(RefCell::new(axrom::Axrom::new(rom, bus_conflicts))))
+        }
+        mapper => Err(EmuError::UnsupportedMapper(mapper)),
+    }
+}
+
+// Whether a discrete mapper's board has bus conflicts, going by the submapper if it says.
+fn bus_conflicts(rom: &Rom, default: bool) -> bool {
+    match rom.submapper_number {
+        1 => false,
+        2 => true,
+        _ => default,
+    }
+}
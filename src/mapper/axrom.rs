@@ -0,0 +1,114 @@
+use crate::mapper::{Mapper, PrgRam};
+use crate::rom::{MirroringType, Rom};
+use crate::savestate::{StateReader, StateWriter};
+
+// Mapper 7: AxROM. A switchable 32KB PRG bank, 8KB of CHR RAM, and single-screen mirroring with
+// the nametable page picked by the same register.
+pub struct Axrom {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    bus_conflicts: bool, // the ROM drives the data bus during writes, ANDing with the value written
+    bank_select: u8,     // PRG bank (bits 0-2), nametable page (bit 4)
+}
+
+impl Axrom {
+    pub fn new(rom: Rom, bus_conflicts: bool) -> Self {
+        let prg_ram = PrgRam::new(&rom);
+        let chr_is_ram = rom.chr_rom.is_empty();
+
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram,
+            chr: if chr_is_ram {
+                vec![0u8; 0x2000]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            bus_conflicts,
+            bank_select: 0,
+        }
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        let bank_count = std::cmp::max(self.prg_rom.len() / 0x8000, 1);
+        let bank = usize::from(self.bank_select & 0x7) % bank_count;
+        (bank * 0x8000 + usize::from(addr - 0x8000)) % self.prg_rom.len()
+    }
+}
+
+impl Mapper for Axrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_addr(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.bank_select = if self.bus_conflicts {
+                val & self.prg_rom[self.prg_addr(addr)]
+            } else {
+                val
+            };
+        } else if addr >= 0x6000 {
+            self.prg_ram.write(addr, val);
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[usize::from(addr) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_is_ram {
+            let chr_addr = usize::from(addr) % self.chr.len();
+            self.chr[chr_addr] = val;
+        }
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        if self.bank_select & 0x10 == 0 {
+            MirroringType::SingleScreenLower
+        } else {
+            MirroringType::SingleScreenUpper
+        }
+    }
+
+    fn power_cycle(&mut self) {
+        self.bank_select = 0;
+        if self.chr_is_ram {
+            self.chr = vec![0u8; 0x2000];
+        }
+        self.prg_ram.power_cycle();
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_data()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_data_mut()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram.data);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+        w.write_u8(self.bank_select);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        r.read_bytes(&mut self.prg_ram.data)?;
+        if self.chr_is_ram {
+            r.read_bytes(&mut self.chr)?;
+        }
+        self.bank_select = r.read_u8()?;
+        Ok(())
+    }
+}
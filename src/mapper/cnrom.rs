@@ -0,0 +1,96 @@
+use crate::mapper::{Mapper, PrgRam};
+use crate::rom::{MirroringType, Rom};
+use crate::savestate::{StateReader, StateWriter};
+
+// Mapper 3: CNROM. PRG is fixed like NROM, and an 8KB CHR ROM bank is switched by writing to
+// $8000-$FFFF.
+pub struct Cnrom {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr_rom: Vec<u8>,
+    mirroring: MirroringType,
+    bus_conflicts: bool, // the ROM drives the data bus during writes, ANDing with the value written
+    chr_bank: u8,
+}
+
+impl Cnrom {
+    pub fn new(rom: Rom, bus_conflicts: bool) -> Self {
+        let prg_ram = PrgRam::new(&rom);
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram,
+            // Nothing should ship without CHR ROM, but don't index into an empty Vec if it does
+            chr_rom: if rom.chr_rom.is_empty() {
+                vec![0u8; 0x2000]
+            } else {
+                rom.chr_rom
+            },
+            mirroring: rom.mirroring,
+            bus_conflicts,
+            chr_bank: 0,
+        }
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        // If only one bank, it's mirrored
+        usize::from(addr - 0x8000) % self.prg_rom.len()
+    }
+}
+
+impl Mapper for Cnrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_addr(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.chr_bank = if self.bus_conflicts {
+                val & self.prg_rom[self.prg_addr(addr)]
+            } else {
+                val
+            };
+        } else if addr >= 0x6000 {
+            self.prg_ram.write(addr, val);
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let bank_count = self.chr_rom.len() / 0x2000;
+        let bank = usize::from(self.chr_bank) % bank_count;
+        self.chr_rom[bank * 0x2000 + usize::from(addr & 0x1FFF)]
+    }
+
+    fn ppu_write(&mut self, _addr: u16, _val: u8) {}
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring
+    }
+
+    fn power_cycle(&mut self) {
+        self.chr_bank = 0;
+        self.prg_ram.power_cycle();
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_data()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_data_mut()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram.data);
+        w.write_u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        r.read_bytes(&mut self.prg_ram.data)?;
+        self.chr_bank = r.read_u8()?;
+        Ok(())
+    }
+}
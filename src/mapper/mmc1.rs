@@ -0,0 +1,261 @@
+use crate::mapper::{Mapper, PrgRam};
+use crate::rom::{MirroringType, Rom};
+use crate::savestate::{StateReader, StateWriter};
+
+// Which board the MMC1 is on. The boards reuse bit 4 of the CHR bank registers for other things
+// when they don't need it for CHR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Board {
+    // Plain SxROM boards: CHR bank registers only select CHR.
+    Standard,
+    // 8KB of CHR RAM; bit 4 of the CHR bank register disables PRG RAM.
+    Snrom,
+    // 512KB of PRG ROM; bit 4 of the CHR bank register selects which 256KB half is used.
+    Surom,
+}
+
+// Mapper 1: the MMC1. Registers are loaded serially, one bit per write, through a 5-bit shift
+// register, and control PRG banking (16KB or 32KB), CHR banking (4KB or 8KB) and mirroring.
+pub struct Mmc1 {
+    board: Board,
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    shift_register: u8,
+    shift_count: u8,
+    control: u8, // mirroring (bits 0-1), PRG bank mode (bits 2-3), CHR bank mode (bit 4)
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8, // PRG bank (bits 0-3), PRG RAM disable (bit 4)
+    cycles: u64,  // CPU cycles since power on
+    last_write_cycle: u64,
+}
+
+impl Mmc1 {
+    pub fn new(rom: Rom) -> Self {
+        let prg_ram = PrgRam::new(&rom);
+        let chr_is_ram = rom.chr_rom.is_empty();
+        let board = if rom.prg_rom.len() > 0x40000 {
+            Board::Surom
+        } else if chr_is_ram {
+            Board::Snrom
+        } else {
+            Board::Standard
+        };
+
+        let mut res = Self {
+            board,
+            prg_rom: rom.prg_rom,
+            prg_ram,
+            chr: if chr_is_ram {
+                vec![0u8; 0x2000]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            shift_register: 0,
+            shift_count: 0,
+            control: 0,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            cycles: 0,
+            last_write_cycle: 0,
+        };
+
+        res.power_cycle();
+
+        res
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        let disabled = self.prg_bank & 0x10 != 0
+            || (self.board == Board::Snrom && self.chr_bank_0 & 0x10 != 0);
+        !disabled
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        // SUROM picks the 256KB half with the CHR bank register; banks below only see 256KB
+        let outer_bank = if self.board == Board::Surom {
+            usize::from(self.chr_bank_0 & 0x10)
+        } else {
+            0
+        };
+        let bank = usize::from(self.prg_bank & 0x0F);
+        let last_bank = 0x0F;
+
+        let bank_16k = match (self.control >> 2) & 0x3 {
+            // Switch 32KB at 0x8000, ignoring the low bit of the bank number
+            0 | 1 => (bank & !1) | usize::from(addr >= 0xC000),
+            // Fix the first bank at 0x8000, switch 16KB at 0xC000
+            2 => {
+                if addr < 0xC000 {
+                    0
+                } else {
+                    bank
+                }
+            }
+            // Fix the last bank at 0xC000, switch 16KB at 0x8000
+            _ => {
+                if addr < 0xC000 {
+                    bank
+                } else {
+                    last_bank
+                }
+            }
+        };
+
+        let bank_count = self.prg_rom.len() / 0x4000;
+        let bank_16k = (outer_bank | bank_16k) % bank_count;
+        bank_16k * 0x4000 + usize::from(addr & 0x3FFF)
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        let bank_4k = if self.control & 0x10 == 0 {
+            // Switch 8KB at a time, ignoring the low bit of the bank number
+            usize::from(self.chr_bank_0 & !1) | usize::from(addr >= 0x1000)
+        } else if addr < 0x1000 {
+            usize::from(self.chr_bank_0)
+        } else {
+            usize::from(self.chr_bank_1)
+        };
+
+        let bank_count = self.chr.len() / 0x1000;
+        (bank_4k % bank_count) * 0x1000 + usize::from(addr & 0x0FFF)
+    }
+
+    // Load a full 5-bit value into the register selected by bits 13 and 14 of the address.
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = val,
+            0xA000..=0xBFFF => self.chr_bank_0 = val,
+            0xC000..=0xDFFF => self.chr_bank_1 = val,
+            _ => self.prg_bank = val,
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_addr(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.write(addr, val),
+            0x8000..=0xFFFF => {
+                // The MMC1 ignores a write on the cycle right after another one, which is what
+                // read-modify-write instructions do when they write the old value back first.
+                let consecutive = self.cycles - self.last_write_cycle < 2;
+                self.last_write_cycle = self.cycles;
+                if consecutive {
+                    return;
+                }
+
+                // Writing with bit 7 set resets the shift register and locks the last PRG bank
+                // at 0xC000
+                if val & 0x80 != 0 {
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    return;
+                }
+
+                // Bits arrive low bit first, so shift in from the top
+                self.shift_register = (self.shift_register >> 1) | ((val & 0x1) << 4);
+                self.shift_count += 1;
+
+                if self.shift_count == 5 {
+                    self.write_register(addr, self.shift_register);
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_addr(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_is_ram {
+            let chr_addr = self.chr_addr(addr);
+            self.chr[chr_addr] = val;
+        }
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        match self.control & 0x3 {
+            0 => MirroringType::SingleScreenLower,
+            1 => MirroringType::SingleScreenUpper,
+            2 => MirroringType::Vertical,
+            _ => MirroringType::Horizontal,
+        }
+    }
+
+    fn cpu_tick(&mut self) {
+        self.cycles += 1;
+    }
+
+    fn power_cycle(&mut self) {
+        self.shift_register = 0;
+        self.shift_count = 0;
+        self.control = 0x0C;
+        self.chr_bank_0 = 0;
+        self.chr_bank_1 = 0;
+        self.prg_bank = 0;
+        self.cycles = 0;
+        self.last_write_cycle = 0;
+
+        self.prg_ram.power_cycle();
+        if self.chr_is_ram {
+            self.chr = vec![0u8; 0x2000];
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_data()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_data_mut()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram.data);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+        w.write_u8(self.shift_register);
+        w.write_u8(self.shift_count);
+        w.write_u8(self.control);
+        w.write_u8(self.chr_bank_0);
+        w.write_u8(self.chr_bank_1);
+        w.write_u8(self.prg_bank);
+        w.write_u64(self.cycles);
+        w.write_u64(self.last_write_cycle);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        r.read_bytes(&mut self.prg_ram.data)?;
+        if self.chr_is_ram {
+            r.read_bytes(&mut self.chr)?;
+        }
+        self.shift_register = r.read_u8()?;
+        self.shift_count = r.read_u8()?;
+        self.control = r.read_u8()?;
+        self.chr_bank_0 = r.read_u8()?;
+        self.chr_bank_1 = r.read_u8()?;
+        self.prg_bank = r.read_u8()?;
+        self.cycles = r.read_u64()?;
+        self.last_write_cycle = r.read_u64()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+use crate::mapper::{Mapper, PrgRam};
+use crate::rom::{MirroringType, Rom};
+use crate::savestate::{StateReader, StateWriter};
+
+// The two revisions of the MMC3 differ in when the scanline counter raises an IRQ.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IrqVariant {
+    // MMC3B/MMC3C (made by Sharp): an IRQ fires on every clock that leaves the counter at 0, so a
+    // latch of 0 fires on every scanline.
+    Sharp,
+    // MMC3A (made by NEC): an IRQ only fires when the counter is decremented to 0 or reloaded
+    // through $C001, so a latch of 0 fires once.
+    Nec,
+}
+
+// Mapper 4: the MMC3. Two switchable 8KB PRG banks, two 2KB and four 1KB CHR banks, mirroring
+// control, 8KB of PRG RAM, and a scanline counter clocked by rises of PPU A12 that can raise
+// IRQs.
+pub struct Mmc3 {
+    irq_variant: IrqVariant,
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    four_screen: bool,
+    bank_select: u8, // register to update (bits 0-2), PRG mode (bit 6), CHR A12 inversion (bit 7)
+    bank_registers: [u8; 8], // R0-R5 are CHR banks, R6-R7 are PRG banks
+    horizontal_mirroring: bool,
+    // Writes disabled (bit 6), enabled (bit 7). It powers on enabled and writable, since plenty of
+    // games use PRG RAM without ever writing $A001.
+    prg_ram_protect: u8,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    a12: bool,          // last level seen on PPU A12
+    a12_low_since: u64, // CPU cycle A12 last went low
+    cycles: u64,        // CPU cycles since power on
+}
+
+impl Mmc3 {
+    // A12 has to stay low for this many CPU cycles before a rise clocks the counter. This filters
+    // out the rises between individual sprite fetches.
+    const A12_FILTER_CYCLES: u64 = 3;
+
+    pub fn new(rom: Rom, irq_variant: IrqVariant) -> Self {
+        let prg_ram = PrgRam::new(&rom);
+        let chr_is_ram = rom.chr_rom.is_empty();
+
+        let mut res = Self {
+            irq_variant,
+            prg_rom: rom.prg_rom,
+            prg_ram,
+            chr: if chr_is_ram {
+                vec![0u8; 0x2000]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            four_screen: matches!(rom.mirroring, MirroringType::FourScreen),
+            bank_select: 0,
+            bank_registers: [0; 8],
+            horizontal_mirroring: false,
+            prg_ram_protect: 0x80,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12: false,
+            a12_low_since: 0,
+            cycles: 0,
+        };
+
+        res.power_cycle();
+
+        res
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        let bank_count = self.prg_rom.len() / 0x2000;
+        let second_last = bank_count - 2;
+        let last = bank_count - 1;
+        let r6 = usize::from(self.bank_registers[6]);
+        let r7 = usize::from(self.bank_registers[7]);
+
+        let bank = match (addr, self.bank_select & 0x40 != 0) {
+            (0x8000..=0x9FFF, false) => r6,
+            (0x8000..=0x9FFF, true) => second_last,
+            (0xA000..=0xBFFF, _) => r7,
+            (0xC000..=0xDFFF, false) => second_last,
+            (0xC000..=0xDFFF, true) => r6,
+            _ => last,
+        };
+
+        (bank % bank_count) * 0x2000 + usize::from(addr & 0x1FFF)
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        // A12 inversion swaps which half of the pattern tables gets the 2KB banks
+        let addr = if self.bank_select & 0x80 != 0 {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+
+        let bank_1k = match addr {
+            0x0000..=0x07FF => usize::from(self.bank_registers[0] & !1) + usize::from(addr >> 10),
+            0x0800..=0x0FFF => {
+                usize::from(self.bank_registers[1] & !1) + usize::from((addr >> 10) & 1)
+            }
+            _ => usize::from(self.bank_registers[2 + usize::from((addr >> 10) & 0x3)]),
+        };
+
+        let bank_count = self.chr.len() / 0x400;
+        (bank_1k % bank_count) * 0x400 + usize::from(addr & 0x03FF)
+    }
+
+    fn clock_irq_counter(&mut self) {
+        let old_counter = self.irq_counter;
+        let reloading = self.irq_reload;
+
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+        } else {
+            self.irq_counter -= 1;
+        }
+        self.irq_reload = false;
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            match self.irq_variant {
+                IrqVariant::Sharp => self.irq_pending = true,
+                IrqVariant::Nec => {
+                    if old_counter != 0 || reloading {
+                        self.irq_pending = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_protect & 0x80 != 0 => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_addr(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        let even = addr & 0x1 == 0;
+
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_protect & 0xC0 == 0x80 => self.prg_ram.write(addr, val),
+            0x8000..=0x9FFF if even => self.bank_select = val,
+            0x8000..=0x9FFF => {
+                self.bank_registers[usize::from(self.bank_select & 0x7)] = val;
+            }
+            0xA000..=0xBFFF if even => self.horizontal_mirroring = val & 0x1 != 0,
+            0xA000..=0xBFFF => self.prg_ram_protect = val,
+            0xC000..=0xDFFF if even => self.irq_latch = val,
+            0xC000..=0xDFFF => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            0xE000..=0xFFFF if even => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            0xE000..=0xFFFF => self.irq_enabled = true,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_addr(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_is_ram {
+            let chr_addr = self.chr_addr(addr);
+            self.chr[chr_addr] = val;
+        }
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        if self.four_screen {
+            MirroringType::FourScreen
+        } else if self.horizontal_mirroring {
+            MirroringType::Horizontal
+        } else {
+            MirroringType::Vertical
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn cpu_tick(&mut self) {
+        self.cycles += 1;
+    }
+
+    fn ppu_address(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+
+        if a12 && !self.a12 && self.cycles - self.a12_low_since >= Self::A12_FILTER_CYCLES {
+            self.clock_irq_counter();
+        } else if !a12 && self.a12 {
+            self.a12_low_since = self.cycles;
+        }
+
+        self.a12 = a12;
+    }
+
+    fn power_cycle(&mut self) {
+        self.bank_select = 0;
+        self.bank_registers = [0, 2, 4, 5, 6, 7, 0, 1];
+        self.horizontal_mirroring = false;
+        self.prg_ram_protect = 0x80;
+        self.irq_latch = 0;
+        self.irq_counter = 0;
+        self.irq_reload = false;
+        self.irq_enabled = false;
+        self.irq_pending = false;
+        self.a12 = false;
+        self.a12_low_since = 0;
+        self.cycles = 0;
+
+        self.prg_ram.power_cycle();
+        if self.chr_is_ram {
+            self.chr = vec![0u8; 0x2000];
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_data()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_data_mut()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram.data);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+        w.write_u8(self.bank_select);
+        w.write_bytes(&self.bank_registers);
+        w.write_bool(self.horizontal_mirroring);
+        w.write_u8(self.prg_ram_protect);
+        w.write_u8(self.irq_latch);
+        w.write_u8(self.irq_counter);
+        w.write_bool(self.irq_reload);
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.irq_pending);
+        w.write_bool(self.a12);
+        w.write_u64(self.a12_low_since);
+        w.write_u64(self.cycles);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        r.read_bytes(&mut self.prg_ram.data)?;
+        if self.chr_is_ram {
+            r.read_bytes(&mut self.chr)?;
+        }
+        self.bank_select = r.read_u8()?;
+        r.read_bytes(&mut self.bank_registers)?;
+        self.horizontal_mirroring = r.read_bool()?;
+        self.prg_ram_protect = r.read_u8()?;
+        self.irq_latch = r.read_u8()?;
+        self.irq_counter = r.read_u8()?;
+        self.irq_reload = r.read_bool()?;
+        self.irq_enabled = r.read_bool()?;
+        self.irq_pending = r.read_bool()?;
+        self.a12 = r.read_bool()?;
+        self.a12_low_since = r.read_u64()?;
+        self.cycles = r.read_u64()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use crate::mapper::{Mapper, PrgRam};
+use crate::rom::{MirroringType, Rom};
+use crate::savestate::{StateReader, StateWriter};
+
+// Mapper 0: no bank switching. 16KB or 32KB of PRG ROM at $8000 (16KB is mirrored at $C000), and
+// 8KB of CHR ROM, or CHR RAM if the cartridge has no CHR ROM.
+pub struct Nrom {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    mirroring: MirroringType,
+}
+
+impl Nrom {
+    pub fn new(rom: Rom) -> Self {
+        let prg_ram = PrgRam::new(&rom);
+        let chr_is_ram = rom.chr_rom.is_empty();
+
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram,
+            chr: if chr_is_ram {
+                vec![0u8; 0x2000]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            mirroring: rom.mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => {
+                // If only one bank, it's mirrored
+                let prg_addr = usize::from(addr - 0x8000) % self.prg_rom.len();
+                Some(self.prg_rom[prg_addr])
+            }
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if (0x6000..=0x7FFF).contains(&addr) {
+            self.prg_ram.write(addr, val);
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[usize::from(addr) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_is_ram {
+            let chr_addr = usize::from(addr) % self.chr.len();
+            self.chr[chr_addr] = val;
+        }
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring
+    }
+
+    fn power_cycle(&mut self) {
+        if self.chr_is_ram {
+            self.chr = vec![0u8; 0x2000];
+        }
+        self.prg_ram.power_cycle();
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_data()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_data_mut()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram.data);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        r.read_bytes(&mut self.prg_ram.data)?;
+        if self.chr_is_ram {
+            r.read_bytes(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use crate::mapper::{Mapper, PrgRam};
+use crate::rom::{MirroringType, Rom};
+use crate::savestate::{StateReader, StateWriter};
+
+// Mapper 2: UxROM. A switchable 16KB PRG bank at $8000 and the last bank fixed at $C000. CHR is
+// usually 8KB of RAM.
+pub struct Uxrom {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    mirroring: MirroringType,
+    bus_conflicts: bool, // the ROM drives the data bus during writes, ANDing with the value written
+    prg_bank: u8,
+}
+
+impl Uxrom {
+    pub fn new(rom: Rom, bus_conflicts: bool) -> Self {
+        let prg_ram = PrgRam::new(&rom);
+        let chr_is_ram = rom.chr_rom.is_empty();
+
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram,
+            chr: if chr_is_ram {
+                vec![0u8; 0x2000]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            mirroring: rom.mirroring,
+            bus_conflicts,
+            prg_bank: 0,
+        }
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        let bank_count = self.prg_rom.len() / 0x4000;
+        let bank = if addr < 0xC000 {
+            usize::from(self.prg_bank) % bank_count
+        } else {
+            bank_count - 1
+        };
+        bank * 0x4000 + usize::from(addr & 0x3FFF)
+    }
+}
+
+impl Mapper for Uxrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram.read(addr),
+            0x8000..=0xFFFF => Some(self.prg_rom[self.prg_addr(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.prg_bank = if self.bus_conflicts {
+                val & self.prg_rom[self.prg_addr(addr)]
+            } else {
+                val
+            };
+        } else if addr >= 0x6000 {
+            self.prg_ram.write(addr, val);
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[usize::from(addr) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_is_ram {
+            let chr_addr = usize::from(addr) % self.chr.len();
+            self.chr[chr_addr] = val;
+        }
+    }
+
+    fn mirroring(&self) -> MirroringType {
+        self.mirroring
+    }
+
+    fn power_cycle(&mut self) {
+        self.prg_bank = 0;
+        if self.chr_is_ram {
+            self.chr = vec![0u8; 0x2000];
+        }
+        self.prg_ram.power_cycle();
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram.battery_data()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram.battery_data_mut()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.prg_ram.data);
+        if self.chr_is_ram {
+            w.write_bytes(&self.chr);
+        }
+        w.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        r.read_bytes(&mut self.prg_ram.data)?;
+        if self.chr_is_ram {
+            r.read_bytes(&mut self.chr)?;
+        }
+        self.prg_bank = r.read_u8()?;
+        Ok(())
+    }
+}
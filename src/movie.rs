@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind};
+
+// A single frame of recorded input.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MovieFrame {
+    pub soft_reset: bool,
+    // Buttons held on controller 1, in the order the standard controller reports them (bit 0 is
+    // A, bit 7 is Right).
+    pub buttons: u8,
+}
+
+// Input movie in FCEUX's text .fm2 format. Only controller 1 is played back.
+#[derive(Debug)]
+pub struct Movie {
+    frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    pub fn new(filename: &str) -> Result<Movie, std::io::Error> {
+        let f = BufReader::new(File::open(filename)?);
+
+        let mut frames = Vec::new();
+
+        for line in f.lines() {
+            let line = line?;
+
+            // Everything that isn't an input log line is a "key value" header line.
+            if !line.starts_with('|') {
+                if line.trim() == "binary 1" {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "Binary fm2 movies are not supported",
+                    ));
+                }
+                continue;
+            }
+
+            // Input log lines look like |commands|port0|port1|port2|
+            let fields = line.split('|').collect::<Vec<_>>();
+            if fields.len() < 3 {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Malformed movie input line: {}", line),
+                ));
+            }
+
+            let commands = fields[1].trim().parse::<u32>().unwrap_or(0);
+
+            // Gamepad buttons are logged as RLDUTSBA, where anything other than '.' or ' ' means
+            // the button is held.
+            let mut buttons = 0u8;
+            for (i, c) in fields[2].chars().take(8).enumerate() {
+                if c != '.' && c != ' ' {
+                    buttons |= 1 << (7 - i);
+                }
+            }
+
+            frames.push(MovieFrame {
+                soft_reset: commands & 0x1 != 0,
+                buttons,
+            });
+        }
+
+        Ok(Movie { frames })
+    }
+
+    pub fn frame(&self, idx: usize) -> Option<MovieFrame> {
+        self.frames.get(idx).copied()
+    }
+}
@@ -0,0 +1,191 @@
+use crate::apu::{Apu, Channel};
+use crate::bus::Bus;
+use crate::controller::{Controller, MovieStep};
+use crate::cpu::Cpu;
+use crate::error::EmuError;
+use crate::frontend::{AudioSink, InputSource, VideoSink};
+use crate::mapper;
+use crate::movie::Movie;
+use crate::ppu::Ppu;
+use crate::rom::{Region, Rom};
+use crate::savestate;
+
+use std::rc::Rc;
+
+// The whole console: CPU, and through the CPU's bus, RAM, PPU, controller and cartridge. This is
+// the entry point for frontends.
+pub struct Nes {
+    pub cpu: Cpu,
+    region: Region,
+    frame_count: u64,
+    movie_finished: Option<usize>, // frames the movie played, if it ran out since last asked
+    audio_samples: Vec<f32>,
+    video: Box<dyn VideoSink>,
+    audio: Box<dyn AudioSink>,
+}
+
+impl Nes {
+    // Build a console with the given cartridge inserted and power it on. Finished frames and audio
+    // go to the given sinks, and controller 1 is read from the given input source.
+    pub fn new(
+        rom: Rom,
+        region: Region,
+        video: Box<dyn VideoSink>,
+        audio: Box<dyn AudioSink>,
+        input: Box<dyn InputSource>,
+    ) -> Result<Self, EmuError> {
+        let mapper = mapper::new(rom)?;
+        let ppu = Ppu::new(region, Rc::clone(&mapper));
+        let apu = Apu::new(region, audio.sample_rate());
+        let bus = Bus::new(mapper, ppu, apu, Controller::new(input));
+        let mut res = Self {
+            cpu: Cpu::new(bus),
+            region,
+            frame_count: 0,
+            movie_finished: None,
+            audio_samples: Vec::new(),
+            video,
+            audio,
+        };
+
+        res.power_cycle();
+
+        Ok(res)
+    }
+
+    // Swap the cartridge and power cycle the console. On error the current cartridge stays in.
+    pub fn load_rom(&mut self, rom: Rom) -> Result<(), EmuError> {
+        let mapper = mapper::new(rom)?;
+
+        self.cpu.bus.insert_cartridge(mapper);
+        self.power_cycle();
+
+        Ok(())
+    }
+
+    // Play back a recorded movie on controller 1 instead of reading the input source.
+    pub fn set_movie(&mut self, movie: Movie) {
+        self.cpu.bus.controller.set_movie(movie);
+    }
+
+    // If the movie has run out of input since the last call, the number of frames it played.
+    pub fn take_movie_finished(&mut self) -> Option<usize> {
+        self.movie_finished.take()
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+    }
+
+    pub fn power_cycle(&mut self) {
+        self.cpu.power_cycle();
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    // Number of frames completed since the console was created.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    // Run until the PPU finishes the current frame, then hand the frame and its audio to the
+    // sinks.
+    pub fn run_frame(&mut self) -> Result<(), EmuError> {
+        self.audio_samples.clear();
+
+        while !self.step_instruction()? {}
+
+        std::mem::swap(&mut self.audio_samples, &mut self.cpu.bus.apu.samples);
+        self.video.present_frame(&self.cpu.bus.ppu.framebuffer);
+        self.audio.queue_samples(&self.audio_samples);
+        self.cpu
+            .bus
+            .apu
+            .set_rate_adjustment(self.audio.rate_adjustment());
+
+        Ok(())
+    }
+
+    // Run the CPU's next instruction (and the interrupt after it, if one was polled); the rest of
+    // the console runs along with every bus access it makes. Returns true if the PPU finished a
+    // frame along the way. If the CPU fails, its program counter doesn't advance, so stepping again
+    // reports the same error.
+    pub fn step_instruction(&mut self) -> Result<bool, EmuError> {
+        self.cpu.step()?;
+
+        let new_frame = std::mem::take(&mut self.cpu.bus.frame_complete);
+        if new_frame {
+            self.frame_count += 1;
+
+            match self.cpu.bus.controller.next_movie_frame() {
+                MovieStep::Continue => {}
+                MovieStep::SoftReset => self.reset(),
+                MovieStep::Finished(frames) => self.movie_finished = Some(frames),
+            }
+        }
+
+        Ok(new_frame)
+    }
+
+    // Palette indices (into ppu::PALETTE) of the last rendered frame, one byte per pixel, row by
+    // row.
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.cpu.bus.ppu.framebuffer
+    }
+
+    // Audio produced during the last frame, at the audio sink's sample rate.
+    pub fn audio_samples(&self) -> &[f32] {
+        &self.audio_samples
+    }
+
+    // Mix an APU channel quieter, from 0.0 (silent) to 1.0 (as the console plays it), to isolate
+    // or leave out channels. Kept across resets and power cycles.
+    pub fn set_channel_volume(&mut self, channel: Channel, volume: f32) {
+        self.cpu.bus.apu.set_channel_volume(channel, volume);
+    }
+
+    pub fn channel_volume(&self, channel: Channel) -> f32 {
+        self.cpu.bus.apu.channel_volume(channel)
+    }
+
+    pub fn set_channel_muted(&mut self, channel: Channel, muted: bool) {
+        self.cpu.bus.apu.set_channel_muted(channel, muted);
+    }
+
+    pub fn channel_muted(&self, channel: Channel) -> bool {
+        self.cpu.bus.apu.channel_muted(channel)
+    }
+
+    // Contents of the cartridge's battery-backed RAM, if it has any, for keeping in a save file.
+    pub fn battery_ram(&self) -> Option<Vec<u8>> {
+        self.cpu
+            .bus
+            .mapper
+            .borrow()
+            .battery_ram()
+            .map(|ram| ram.to_vec())
+    }
+
+    // Restore the cartridge's battery-backed RAM from a save file. Returns false if the cartridge
+    // has no battery or the save is a different size, in which case as much as fits is loaded.
+    pub fn load_battery_ram(&mut self, data: &[u8]) -> bool {
+        match self.cpu.bus.mapper.borrow_mut().battery_ram_mut() {
+            Some(ram) => {
+                let len = std::cmp::min(ram.len(), data.len());
+                ram[..len].copy_from_slice(&data[..len]);
+                ram.len() == data.len()
+            }
+            None => false,
+        }
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        savestate::save(&self.cpu)
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), std::io::Error> {
+        savestate::load(&mut self.cpu, data)
+    }
+}
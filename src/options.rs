@@ -0,0 +1,169 @@
+use emulator::rom::Region;
+
+pub const USAGE: &str = "Usage: emulator [OPTIONS] <ROM>
+
+Options:
+  -s, --scale <N>        Window scale factor (default: 3)
+  -r, --region <REGION>  Console region, either ntsc or pal (default: the ROM's region)
+      --headless         Run without a window or audio, as fast as possible
+      --vsync            Pace frames with the display's vertical sync instead of a timer
+      --unthrottled      Run as fast as possible instead of at the console's frame rate
+      --audio-sync       Pace frames by the audio device's clock instead of a timer
+      --sample-rate <HZ> Audio sample rate (default: 44100)
+  -f, --frames <N>       Exit after emulating N frames
+      --state <FILE>     Load a savestate before starting (F5 saves, F7 reloads)
+      --movie <FILE>     Play back an FCEUX .fm2 input movie on controller 1
+      --patch <FILE>     Apply an IPS, UPS or BPS patch to the ROM (default: a .ips, .ups or
+                         .bps file next to the ROM with the same name, if there is one)
+      --no-patch         Don't apply a patch found next to the ROM
+      --rom-db <FILE>    Correct ROM headers with the entries in FILE, on top of the built-in
+                         database
+  -h, --help             Print this message";
+
+// How the windowed frontend keeps emulation running at the console's frame rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pacing {
+    // Sleep until each frame is due (60.0988 Hz on NTSC, 50.007 Hz on PAL)
+    Timer,
+    // Block on the display's vertical sync when presenting a frame
+    Vsync,
+    // Don't wait at all
+    Unthrottled,
+    // Block when queueing audio until the audio device has caught up
+    Audio,
+}
+
+// Window-only settings go unused when the binary is built without SDL
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+#[derive(Debug)]
+pub struct Options {
+    pub rom_path: String,
+    pub scale: u32,
+    pub region: Option<Region>, // None to use the ROM's
+    pub headless: bool,
+    pub pacing: Pacing,
+    pub sample_rate: u32,
+    pub frame_limit: Option<u64>,
+    pub state_path: Option<String>,
+    pub movie_path: Option<String>,
+    pub patch_path: Option<String>,
+    pub find_patch: bool, // look for a patch next to the ROM if none was given
+    pub rom_db_path: Option<String>,
+}
+
+// Result of parsing the command line: either options to run with, or a request for the usage
+// message.
+#[derive(Debug)]
+pub enum Command {
+    Run(Options),
+    Help,
+}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+        let mut rom_path = None;
+        let mut scale = 3;
+        let mut region = None;
+        let mut headless = false;
+        let mut pacing = Pacing::Timer;
+        let mut sample_rate = 44_100;
+        let mut frame_limit = None;
+        let mut state_path = None;
+        let mut movie_path = None;
+        let mut patch_path = None;
+        let mut find_patch = true;
+        let mut rom_db_path = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "-s" | "--scale" => {
+                    let val = Self::value(&arg, args.next())?;
+                    scale = match val.parse::<u32>() {
+                        Ok(scale) if scale > 0 => scale,
+                        _ => return Err(format!("invalid scale '{}'", val)),
+                    };
+                }
+                "-r" | "--region" => {
+                    let val = Self::value(&arg, args.next())?;
+                    region = match val.to_ascii_lowercase().as_str() {
+                        "ntsc" => Some(Region::Ntsc),
+                        "pal" => Some(Region::Pal),
+                        _ => return Err(format!("invalid region '{}'", val)),
+                    };
+                }
+                "--headless" => headless = true,
+                "--vsync" => pacing = Pacing::Vsync,
+                "--unthrottled" => pacing = Pacing::Unthrottled,
+                "--audio-sync" => pacing = Pacing::Audio,
+                "--sample-rate" => {
+                    let val = Self::value(&arg, args.next())?;
+                    sample_rate = match val.parse::<u32>() {
+                        Ok(rate) if (8_000..=192_000).contains(&rate) => rate,
+                        _ => return Err(format!("invalid sample rate '{}'", val)),
+                    };
+                }
+                "-f" | "--frames" => {
+                    let val = Self::value(&arg, args.next())?;
+                    frame_limit = match val.parse::<u64>() {
+                        Ok(frames) if frames > 0 => Some(frames),
+                        _ => return Err(format!("invalid frame count '{}'", val)),
+                    };
+                }
+                "--state" => state_path = Some(Self::value(&arg, args.next())?),
+                "--movie" => movie_path = Some(Self::value(&arg, args.next())?),
+                "--patch" => patch_path = Some(Self::value(&arg, args.next())?),
+                "--no-patch" => find_patch = false,
+                "--rom-db" => rom_db_path = Some(Self::value(&arg, args.next())?),
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+                _ => {
+                    if rom_path.is_some() {
+                        return Err(format!("unexpected argument '{}'", arg));
+                    }
+                    rom_path = Some(arg);
+                }
+            }
+        }
+
+        let rom_path = rom_path.ok_or_else(|| String::from("no ROM file given"))?;
+
+        Ok(Command::Run(Options {
+            rom_path,
+            scale,
+            region,
+            headless,
+            pacing,
+            sample_rate,
+            frame_limit,
+            state_path,
+            movie_path,
+            patch_path,
+            find_patch,
+            rom_db_path,
+        }))
+    }
+
+    fn value(flag: &str, val: Option<String>) -> Result<String, String> {
+        val.ok_or_else(|| format!("option '{}' requires a value", flag))
+    }
+
+    // Battery-backed RAM is kept next to the ROM, like other emulators do.
+    pub fn save_file_path(&self) -> String {
+        std::path::Path::new(&self.rom_path)
+            .with_extension("sav")
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    // Savestates are written next to the ROM unless a state file was given explicitly.
+    #[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+    pub fn quicksave_path(&self) -> String {
+        match &self.state_path {
+            Some(path) => path.clone(),
+            None => std::path::Path::new(&self.rom_path)
+                .with_extension("state")
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+}
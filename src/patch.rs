@@ -0,0 +1,408 @@
+// Soft-patching: applying IPS, UPS and BPS patches to a ROM file as it's loaded, so translations
+// and hacks can be played without keeping a patched copy. Patches apply to the whole file,
+// header included, which is how NES patches are made.
+
+use crate::checksum;
+use crate::error::EmuError;
+
+use std::convert::TryInto;
+
+// Largest file a UPS or BPS patch may produce. The sizes in a patch's header come before anything
+// can be checked against them, so they're capped rather than trusted with an allocation. The
+// biggest NES ROMs are a few MB.
+const MAX_TARGET_SIZE: usize = 64 * 1024 * 1024;
+
+// Apply a patch to a ROM file, working out the patch format from its magic number.
+pub fn apply(file: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmuError> {
+    if patch.starts_with(b"PATCH") {
+        apply_ips(file, patch)
+    } else if patch.starts_with(b"UPS1") {
+        apply_ups(file, patch)
+    } else if patch.starts_with(b"BPS1") {
+        apply_bps(file, patch)
+    } else {
+        Err(invalid("not an IPS, UPS or BPS patch"))
+    }
+}
+
+fn invalid(reason: &str) -> EmuError {
+    EmuError::InvalidPatch(reason.to_string())
+}
+
+// Reads through a patch, erroring rather than panicking if the patch ends early.
+struct PatchReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], EmuError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| invalid("patch ends unexpectedly"))?;
+        let res = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(res)
+    }
+
+    fn byte(&mut self) -> Result<u8, EmuError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // Big-endian number, as IPS uses
+    fn big_endian(&mut self, len: usize) -> Result<usize, EmuError> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |res, &byte| (res << 8) | usize::from(byte)))
+    }
+
+    // Variable-length number, as UPS and BPS use: 7 bits per byte, least significant first, with
+    // the top bit marking the last byte. Each continuation also adds one, so that every number
+    // has only one encoding.
+    fn number(&mut self) -> Result<usize, EmuError> {
+        let mut res: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            res = usize::from(byte & 0x7F)
+                .checked_mul(shift)
+                .and_then(|val| res.checked_add(val))
+                .ok_or_else(|| invalid("number in patch is too large"))?;
+            if byte & 0x80 != 0 {
+                return Ok(res);
+            }
+            shift = shift
+                .checked_shl(7)
+                .filter(|&shift| shift != 0)
+                .ok_or_else(|| invalid("number in patch is too large"))?;
+            res = res
+                .checked_add(shift)
+                .ok_or_else(|| invalid("number in patch is too large"))?;
+        }
+    }
+}
+
+// IPS: a list of records, each either a run of bytes or one byte repeated, written at a 24-bit
+// offset. The file grows to fit if a record goes past its end.
+fn apply_ips(file: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmuError> {
+    let mut res = file.to_vec();
+    let mut r = PatchReader::new(patch, 5);
+
+    loop {
+        let offset = r.big_endian(3)?;
+        // "EOF" marks the end, optionally followed by the length to truncate the file to
+        if offset == 0x454F46 {
+            if r.data.len() - r.pos >= 3 {
+                let len = r.big_endian(3)?;
+                res.truncate(len);
+            }
+            return Ok(res);
+        }
+
+        let len = r.big_endian(2)?;
+        let (len, record) = if len == 0 {
+            let len = r.big_endian(2)?;
+            (len, vec![r.byte()?; len])
+        } else {
+            (len, r.bytes(len)?.to_vec())
+        };
+
+        if res.len() < offset + len {
+            res.resize(offset + len, 0);
+        }
+        res[offset..offset + len].copy_from_slice(&record);
+    }
+}
+
+fn check_target_size(target_size: usize) -> Result<(), EmuError> {
+    if target_size > MAX_TARGET_SIZE {
+        return Err(EmuError::InvalidPatch(format!(
+            "patched ROM would be {} bytes, more than the {} allowed",
+            target_size, MAX_TARGET_SIZE
+        )));
+    }
+    Ok(())
+}
+
+// UPS and BPS both end with the CRC32 of the file they apply to, of the file they produce, and of
+// the patch itself. Check the patch and the file it's being applied to, and return the CRC32 the
+// result should have and where the footer starts.
+fn check_crcs(file: &[u8], patch: &[u8]) -> Result<(u32, usize), EmuError> {
+    if patch.len() < 16 {
+        return Err(invalid("patch ends unexpectedly"));
+    }
+    let footer = patch.len() - 12;
+    let crc = |pos: usize| u32::from_le_bytes(patch[pos..pos + 4].try_into().unwrap());
+
+    if checksum::crc32(&patch[..patch.len() - 4]) != crc(footer + 8) {
+        return Err(invalid("patch is corrupt (CRC32 mismatch)"));
+    }
+    let file_crc = checksum::crc32(file);
+    if file_crc != crc(footer) {
+        return Err(EmuError::InvalidPatch(format!(
+            "patch is for a different ROM (ROM CRC32 is {:08X}, patch expects {:08X})",
+            file_crc,
+            crc(footer)
+        )));
+    }
+
+    Ok((crc(footer + 4), footer))
+}
+
+fn check_result(res: Vec<u8>, expected_crc: u32) -> Result<Vec<u8>, EmuError> {
+    if checksum::crc32(&res) != expected_crc {
+        return Err(invalid(
+            "patched ROM doesn't have the CRC32 the patch expects",
+        ));
+    }
+    Ok(res)
+}
+
+// UPS: the sizes of the source and target files, then runs of bytes to XOR into the file, each at
+// an offset relative to the end of the last run.
+fn apply_ups(file: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmuError> {
+    let (target_crc, footer) = check_crcs(file, patch)?;
+    let mut r = PatchReader::new(&patch[..footer], 4);
+
+    let source_size = r.number()?;
+    let target_size = r.number()?;
+    if source_size != file.len() {
+        return Err(invalid("patch is for a different ROM (size mismatch)"));
+    }
+    check_target_size(target_size)?;
+
+    let mut res = file.to_vec();
+    res.resize(target_size, 0);
+
+    let mut pos: usize = 0;
+    while r.pos < footer {
+        pos = pos
+            .checked_add(r.number()?)
+            .ok_or_else(|| invalid("offset in patch is too large"))?;
+        // Each run ends with a 0 byte, which is XORed in as well
+        loop {
+            let byte = r.byte()?;
+            if let Some(target) = res.get_mut(pos) {
+                *target ^= byte;
+            }
+            pos += 1;
+            if byte == 0 {
+                break;
+            }
+        }
+    }
+
+    check_result(res, target_crc)
+}
+
+// BPS: the sizes of the source and target files and some metadata, then commands that build the
+// target file in order, by copying from the source file, the patch, or what's been built so far.
+fn apply_bps(file: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmuError> {
+    let (target_crc, footer) = check_crcs(file, patch)?;
+    let mut r = PatchReader::new(&patch[..footer], 4);
+
+    let source_size = r.number()?;
+    let target_size = r.number()?;
+    let metadata_size = r.number()?;
+    r.bytes(metadata_size)?;
+    if source_size != file.len() {
+        return Err(invalid("patch is for a different ROM (size mismatch)"));
+    }
+    check_target_size(target_size)?;
+
+    let mut res: Vec<u8> = Vec::new();
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+
+    // Copies are relative to the end of the last copy of the same kind, forwards or backwards
+    let relative = |offset: usize, r: &mut PatchReader| -> Result<usize, EmuError> {
+        let val = r.number()?;
+        let res = if val & 1 != 0 {
+            offset.checked_sub(val >> 1)
+        } else {
+            offset.checked_add(val >> 1)
+        };
+        res.ok_or_else(|| invalid("copy offset in patch is out of range"))
+    };
+
+    while r.pos < footer {
+        let command = r.number()?;
+        let len = (command >> 2) + 1;
+        // res never grows past target_size, so this can't underflow like adding could overflow
+        if len > target_size - res.len() {
+            return Err(invalid("patch writes past the end of the patched ROM"));
+        }
+
+        match command & 0x3 {
+            // Copy from the source file at the same position
+            0 => {
+                let start = res.len();
+                let bytes = file
+                    .get(start..start + len)
+                    .ok_or_else(|| invalid("copy in patch is past the end of the ROM"))?;
+                res.extend_from_slice(bytes);
+            }
+            // Copy from the patch
+            1 => res.extend_from_slice(r.bytes(len)?),
+            // Copy from anywhere in the source file
+            2 => {
+                source_offset = relative(source_offset, &mut r)?;
+                let bytes = source_offset
+                    .checked_add(len)
+                    .and_then(|end| file.get(source_offset..end))
+                    .ok_or_else(|| invalid("copy in patch is past the end of the ROM"))?;
+                res.extend_from_slice(bytes);
+                source_offset += len;
+            }
+            // Copy from earlier in the target file. The copy can overlap what it's writing, to
+            // repeat a pattern, so it has to go a byte at a time.
+            _ => {
+                target_offset = relative(target_offset, &mut r)?;
+                for _ in 0..len {
+                    let byte = *res
+                        .get(target_offset)
+                        .ok_or_else(|| invalid("copy in patch is past the end of the output"))?;
+                    res.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if res.len() != target_size {
+        return Err(invalid("patched ROM is the wrong size"));
+    }
+
+    check_result(res, target_crc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &[u8] = b"hello world";
+    const TARGET: &[u8] = b"jello world!!";
+
+    // A UPS/BPS variable-length number
+    fn number(mut val: usize, out: &mut Vec<u8>) {
+        loop {
+            let byte = (val & 0x7F) as u8;
+            val >>= 7;
+            if val == 0 {
+                out.push(0x80 | byte);
+                return;
+            }
+            out.push(byte);
+            val -= 1;
+        }
+    }
+
+    // Add the UPS/BPS footer: source, target and patch CRC32s
+    fn finish(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&checksum::crc32(source).to_le_bytes());
+        patch.extend_from_slice(&checksum::crc32(target).to_le_bytes());
+        let crc = checksum::crc32(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+        patch
+    }
+
+    fn ups_patch(target_size: usize) -> Vec<u8> {
+        let mut patch = b"UPS1".to_vec();
+        number(SOURCE.len(), &mut patch);
+        number(target_size, &mut patch);
+        // 'h' ^ 'j' at 0, then '!' past the end of the source
+        number(0, &mut patch);
+        patch.extend_from_slice(&[b'h' ^ b'j', 0x00]);
+        number(9, &mut patch);
+        patch.extend_from_slice(&[b'!', b'!', 0x00]);
+        finish(patch, SOURCE, TARGET)
+    }
+
+    // A BPS command: the action in the low 2 bits, and the length minus 1 above
+    fn command(len: usize, action: usize) -> usize {
+        ((len - 1) << 2) | action
+    }
+
+    fn bps_patch() -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        number(SOURCE.len(), &mut patch);
+        number(TARGET.len(), &mut patch);
+        number(0, &mut patch);
+        // Read "j" from the patch, copy "ello world" from the source, read "!" from the patch,
+        // then copy it again from the target
+        number(command(1, 1), &mut patch);
+        patch.push(b'j');
+        number(command(10, 0), &mut patch);
+        number(command(1, 1), &mut patch);
+        patch.push(b'!');
+        number(command(1, 3), &mut patch);
+        number(11 << 1, &mut patch);
+        finish(patch, SOURCE, TARGET)
+    }
+
+    #[test]
+    fn ips() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x01, b'j']);
+        // A run of two '!'s at 11
+        patch.extend_from_slice(&[0x00, 0x00, 0x0B, 0x00, 0x00, 0x00, 0x02, b'!']);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(apply(SOURCE, &patch).unwrap(), TARGET);
+
+        // Truncated to 5 bytes after patching
+        patch.extend_from_slice(&[0x00, 0x00, 0x05]);
+        assert_eq!(apply(SOURCE, &patch).unwrap(), b"jello");
+    }
+
+    #[test]
+    fn ips_ending_early() {
+        assert!(apply(SOURCE, b"PATCH\x00\x00\x00\x00\x05ab").is_err());
+    }
+
+    #[test]
+    fn ups() {
+        assert_eq!(apply(SOURCE, &ups_patch(TARGET.len())).unwrap(), TARGET);
+    }
+
+    #[test]
+    fn ups_target_size_capped() {
+        match apply(SOURCE, &ups_patch(usize::MAX >> 8)) {
+            Err(EmuError::InvalidPatch(_)) => {}
+            res => panic!("expected InvalidPatch, got {:?}", res.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn bps() {
+        assert_eq!(apply(SOURCE, &bps_patch()).unwrap(), TARGET);
+    }
+
+    #[test]
+    fn wrong_base_rom() {
+        for patch in [ups_patch(TARGET.len()), bps_patch()].iter() {
+            match apply(b"hello World", patch) {
+                Err(EmuError::InvalidPatch(reason)) => assert!(reason.contains("different ROM")),
+                res => panic!("expected InvalidPatch, got {:?}", res.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_patch() {
+        let mut patch = bps_patch();
+        patch[6] ^= 0x01;
+        assert!(apply(SOURCE, &patch).is_err());
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(apply(SOURCE, b"NOT A PATCH").is_err());
+    }
+}
@@ -1,13 +1,21 @@
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-
+use crate::mapper::Mapper;
 use crate::rom;
+use crate::savestate::{StateReader, StateWriter};
 
+use std::cell::RefCell;
 use std::convert::TryFrom;
+use std::rc::Rc;
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
 
-const SCALE: u32 = 3;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
 
 pub const PALETTE: [Color; 0x40] = [
     Color {
@@ -403,16 +411,18 @@ pub struct Ppu {
     pub oamaddr: u8,
     pub oamdata: u8,
     pub ppudata_buffer: u8,
+    pub io_latch: u8, // last value written to any PPU register, read back from the write-only ones
     pub ppuscroll: u16, // also called t, or temporary vram address, in docs.
-    pub ppuaddr: u16,   // also called v, or current vram address, in docs
-    pub fine_x: u8,     // fine x scroll of the ppu
+    pub ppuaddr: u16, // also called v, or current vram address, in docs
+    pub fine_x: u8,   // fine x scroll of the ppu
     pub two_write_partial: bool,
-    pub vram: [u8; 0x4000],
+    pub vram: [u8; 0x4000], // nametables and palettes; the pattern tables are on the cartridge
     pub oam: [u8; 0x100],
-    pub canvas: Canvas<Window>,
+    pub framebuffer: [u8; SCREEN_WIDTH * SCREEN_HEIGHT], // palette index (into PALETTE) of every pixel of the current frame
+    pub region: rom::Region,
     pub scanline: u16,
     pub cycle: u16,
-    pub nmi_waiting: bool,
+    pub suppress_vblank: bool, // $2002 was read just before vblank, so it won't be set this frame
     pub even_frame: bool,
     pub pattern_table_shift_low: u16, // the low byte of this is where the parallel input is "shifted" in (latched)
     pub pattern_table_shift_high: u16,
@@ -425,19 +435,18 @@ pub struct Ppu {
     pub decoded_attribute_table_bit_low: bool,
     pub decoded_pattern_table_low: u8,
     pub decoded_pattern_table_high: u8,
-    pub mirror_type: rom::MirroringType,
+    pub sprite_count: u8, // number of sprites found on the next scanline (at most 8)
+    pub sprite_indices: [u8; 8], // OAM index of each sprite found
+    pub sprite_zero_on_line: bool, // whether sprite 0 is among them (always in slot 0)
+    pub sprite_patterns_low: [u8; 8],
+    pub sprite_patterns_high: [u8; 8],
+    pub sprite_attributes: [u8; 8],
+    pub sprite_positions: [u8; 8], // x coordinate of each sprite
+    pub mapper: Rc<RefCell<dyn Mapper>>,
 }
 
 impl Ppu {
-    pub fn new(video_subsystem: &sdl2::VideoSubsystem) -> Self {
-        let window = video_subsystem
-            .window("NES Terminal Window", 256 * SCALE, 240 * SCALE)
-            .position_centered()
-            .build()
-            .unwrap();
-
-        let canvas = window.into_canvas().build().unwrap();
-
+    pub fn new(region: rom::Region, mapper: Rc<RefCell<dyn Mapper>>) -> Self {
         Self {
             ppuctrl: 0x0,
             ppumask: 0x0,
@@ -445,16 +454,18 @@ impl Ppu {
             oamaddr: 0x0,
             oamdata: 0x0,
             ppudata_buffer: 0x0,
+            io_latch: 0x0,
             ppuscroll: 0x0,
             ppuaddr: 0x0,
             fine_x: 0x0,
             two_write_partial: false,
             vram: [0; 0x4000],
             oam: [0; 0x100],
-            canvas,
+            framebuffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            region,
             scanline: 0x0,
             cycle: 0x0,
-            nmi_waiting: false,
+            suppress_vblank: false,
             even_frame: false,
             pattern_table_shift_low: 0,
             pattern_table_shift_high: 0,
@@ -467,41 +478,176 @@ impl Ppu {
             decoded_attribute_table_bit_low: false,
             decoded_pattern_table_low: 0,
             decoded_pattern_table_high: 0,
-            mirror_type: rom::MirroringType::FourScreen,
+            sprite_count: 0,
+            sprite_indices: [0; 8],
+            sprite_zero_on_line: false,
+            sprite_patterns_low: [0; 8],
+            sprite_patterns_high: [0; 8],
+            sprite_attributes: [0; 8],
+            sprite_positions: [0; 8],
+            mapper,
         }
     }
 
+    // Return every register, memory and piece of rendering state to its power-on value.
+    pub fn power_on(&mut self) {
+        self.reset();
+        self.ppustatus = 0x0;
+        self.oamaddr = 0x0;
+        self.oamdata = 0x0;
+        self.io_latch = 0x0;
+        self.ppuaddr = 0x0;
+        self.vram = [0; 0x4000];
+        self.oam = [0; 0x100];
+        self.framebuffer = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.pattern_table_shift_low = 0;
+        self.pattern_table_shift_high = 0;
+        self.attribute_table_palette_shift_low = 0;
+        self.attribute_table_palette_latch_low = false;
+        self.attribute_table_palette_shift_high = 0;
+        self.attribute_table_palette_latch_high = false;
+        self.decoded_nametable_byte = 0;
+        self.decoded_attribute_table_bit_high = false;
+        self.decoded_attribute_table_bit_low = false;
+        self.decoded_pattern_table_low = 0;
+        self.decoded_pattern_table_high = 0;
+        self.sprite_count = 0;
+        self.sprite_indices = [0; 8];
+        self.sprite_zero_on_line = false;
+        self.sprite_patterns_low = [0; 8];
+        self.sprite_patterns_high = [0; 8];
+        self.sprite_attributes = [0; 8];
+        self.sprite_positions = [0; 8];
+    }
+
+    // The reset button only clears some of the PPU's registers; memory and OAM are untouched.
+    pub fn reset(&mut self) {
+        self.ppuctrl = 0x0;
+        self.ppumask = 0x0;
+        self.ppudata_buffer = 0x0;
+        self.ppuscroll = 0x0;
+        self.fine_x = 0x0;
+        self.two_write_partial = false;
+        self.scanline = 0x0;
+        self.cycle = 0x0;
+        self.suppress_vblank = false;
+        self.even_frame = false;
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.ppuctrl);
+        w.write_u8(self.ppumask);
+        w.write_u8(self.ppustatus);
+        w.write_u8(self.oamaddr);
+        w.write_u8(self.oamdata);
+        w.write_u8(self.ppudata_buffer);
+        w.write_u8(self.io_latch);
+        w.write_u16(self.ppuscroll);
+        w.write_u16(self.ppuaddr);
+        w.write_u8(self.fine_x);
+        w.write_bool(self.two_write_partial);
+        w.write_bytes(&self.vram);
+        w.write_bytes(&self.oam);
+        w.write_u16(self.scanline);
+        w.write_u16(self.cycle);
+        w.write_bool(self.suppress_vblank);
+        w.write_bool(self.even_frame);
+        w.write_u16(self.pattern_table_shift_low);
+        w.write_u16(self.pattern_table_shift_high);
+        w.write_u8(self.attribute_table_palette_shift_low);
+        w.write_bool(self.attribute_table_palette_latch_low);
+        w.write_u8(self.attribute_table_palette_shift_high);
+        w.write_bool(self.attribute_table_palette_latch_high);
+        w.write_u8(self.decoded_nametable_byte);
+        w.write_bool(self.decoded_attribute_table_bit_high);
+        w.write_bool(self.decoded_attribute_table_bit_low);
+        w.write_u8(self.decoded_pattern_table_low);
+        w.write_u8(self.decoded_pattern_table_high);
+        w.write_u8(self.sprite_count);
+        w.write_bytes(&self.sprite_indices);
+        w.write_bool(self.sprite_zero_on_line);
+        w.write_bytes(&self.sprite_patterns_low);
+        w.write_bytes(&self.sprite_patterns_high);
+        w.write_bytes(&self.sprite_attributes);
+        w.write_bytes(&self.sprite_positions);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), std::io::Error> {
+        self.ppuctrl = r.read_u8()?;
+        self.ppumask = r.read_u8()?;
+        self.ppustatus = r.read_u8()?;
+        self.oamaddr = r.read_u8()?;
+        self.oamdata = r.read_u8()?;
+        self.ppudata_buffer = r.read_u8()?;
+        self.io_latch = r.read_u8()?;
+        self.ppuscroll = r.read_u16()?;
+        self.ppuaddr = r.read_u16()?;
+        self.fine_x = r.read_u8()?;
+        self.two_write_partial = r.read_bool()?;
+        r.read_bytes(&mut self.vram)?;
+        r.read_bytes(&mut self.oam)?;
+        self.scanline = r.read_u16()?;
+        self.cycle = r.read_u16()?;
+        self.suppress_vblank = r.read_bool()?;
+        self.even_frame = r.read_bool()?;
+        self.pattern_table_shift_low = r.read_u16()?;
+        self.pattern_table_shift_high = r.read_u16()?;
+        self.attribute_table_palette_shift_low = r.read_u8()?;
+        self.attribute_table_palette_latch_low = r.read_bool()?;
+        self.attribute_table_palette_shift_high = r.read_u8()?;
+        self.attribute_table_palette_latch_high = r.read_bool()?;
+        self.decoded_nametable_byte = r.read_u8()?;
+        self.decoded_attribute_table_bit_high = r.read_bool()?;
+        self.decoded_attribute_table_bit_low = r.read_bool()?;
+        self.decoded_pattern_table_low = r.read_u8()?;
+        self.decoded_pattern_table_high = r.read_u8()?;
+        self.sprite_count = r.read_u8()?;
+        r.read_bytes(&mut self.sprite_indices)?;
+        self.sprite_zero_on_line = r.read_bool()?;
+        r.read_bytes(&mut self.sprite_patterns_low)?;
+        r.read_bytes(&mut self.sprite_patterns_high)?;
+        r.read_bytes(&mut self.sprite_attributes)?;
+        r.read_bytes(&mut self.sprite_positions)?;
+        Ok(())
+    }
+
     pub fn get_vram_byte_at(&self, addr: u16) -> u8 {
-        let mut actual_addr = addr % 0x4000;
+        let actual_addr = addr % 0x4000;
 
-        // palettes are mirrored from 0x3F00 to 0x4000 every 0x20 bytes
-        if actual_addr >= 0x3F00 && actual_addr < 0x4000 {
-            actual_addr = ((actual_addr - 0x3F00) % 0x20) + 0x3F00;
+        if actual_addr < 0x2000 {
+            let mut mapper = self.mapper.borrow_mut();
+            mapper.ppu_address(actual_addr);
+            return mapper.ppu_read(actual_addr);
         }
 
-        // Data at addresses 0x3000-0x3EFF mirrors 0x2000-0x2EFF
-        if actual_addr >= 0x3000 && actual_addr < 0x3F00 {
-            actual_addr -= 0x1000;
+        if actual_addr < 0x3F00 {
+            self.mapper.borrow_mut().ppu_address(actual_addr);
         }
 
-        if actual_addr >= 0x2000 && actual_addr < 0x3000 {
-            match self.mirror_type {
-                rom::MirroringType::FourScreen => actual_addr &= !0x0C00,
-                rom::MirroringType::Horizontal => actual_addr &= !0x0400,
-                rom::MirroringType::Vertical => actual_addr &= !0x0800,
-            };
+        self.vram[self.vram_index(actual_addr)]
+    }
+
+    pub fn set_vram_byte_at(&mut self, addr: u16, val: u8) {
+        let actual_addr = addr % 0x4000;
+
+        if actual_addr < 0x2000 {
+            let mut mapper = self.mapper.borrow_mut();
+            mapper.ppu_address(actual_addr);
+            mapper.ppu_write(actual_addr, val);
+            return;
         }
 
-        // Mirror 0x3F0{0,4,8,C} at 0x3F1{0,4,8,C}
-        if actual_addr >= 0x3F00 && actual_addr < 0x4000 && actual_addr % 0x4 == 0 {
-            actual_addr = ((actual_addr - 0x3F00) % 0x10) + 0x3F00;
+        if actual_addr < 0x3F00 {
+            self.mapper.borrow_mut().ppu_address(actual_addr);
         }
 
-        self.vram[usize::from(actual_addr)]
+        let idx = self.vram_index(actual_addr);
+        self.vram[idx] = val;
     }
 
-    pub fn set_vram_byte_at(&mut self, addr: u16, val: u8) {
-        let mut actual_addr = addr % 0x4000;
+    // Index into vram of a nametable or palette address (0x2000-0x3FFF), after mirroring.
+    fn vram_index(&self, addr: u16) -> usize {
+        let mut actual_addr = addr;
 
         // palettes are mirrored from 0x3F00 to 0x4000 every 0x20 bytes
         if actual_addr >= 0x3F00 {
@@ -509,24 +655,29 @@ impl Ppu {
         }
 
         // Data at addresses 0x3000-0x3EFF mirrors 0x2000-0x2EFF
-        if actual_addr >= 0x3000 && actual_addr < 0x3F00 {
+        if (0x3000..0x3F00).contains(&actual_addr) {
             actual_addr -= 0x1000;
         }
 
-        if actual_addr >= 0x2000 && actual_addr < 0x3000 {
-            match self.mirror_type {
-                rom::MirroringType::FourScreen => actual_addr &= !0x0C00,
+        if (0x2000..0x3000).contains(&actual_addr) {
+            match self.mapper.borrow().mirroring() {
+                // The cartridge supplies the other 2KB, so all four nametables are distinct
+                rom::MirroringType::FourScreen => {}
                 rom::MirroringType::Horizontal => actual_addr &= !0x0400,
                 rom::MirroringType::Vertical => actual_addr &= !0x0800,
+                rom::MirroringType::SingleScreenLower => actual_addr &= !0x0C00,
+                rom::MirroringType::SingleScreenUpper => {
+                    actual_addr = (actual_addr & !0x0C00) | 0x0400
+                }
             };
         }
 
         // Mirror 0x3F0{0,4,8,C} at 0x3F1{0,4,8,C}
-        if actual_addr >= 0x3F00 && actual_addr < 0x4000 && actual_addr % 0x4 == 0 {
+        if (0x3F00..0x4000).contains(&actual_addr) && actual_addr.is_multiple_of(0x4) {
             actual_addr = ((actual_addr - 0x3F00) % 0x10) + 0x3F00;
         }
 
-        self.vram[usize::from(actual_addr)] = val;
+        usize::from(actual_addr)
     }
 
     pub fn get_oam_byte_at(&mut self, addr: u8) -> u8 {
@@ -537,7 +688,8 @@ impl Ppu {
         self.oam[usize::from(addr)] = val;
     }
 
-    fn get_current_pixel(&mut self, scanline: u16, dot: u16) -> Color {
+    // Returns the index into PALETTE of the pixel at the given position.
+    fn get_current_pixel(&mut self, scanline: u16, dot: u16) -> u8 {
         let palette_x_offset = 7 - self.fine_x; // fine_x of 0 means we want the highest bit of 8-bit attribute_table_palette_shift_{high,low}
         let pattern_x_offset = 15 - self.fine_x; // fine_x of 0 means we actually want the highest bit of 16-bit pattern_table_shift_{high,low}
 
@@ -557,118 +709,26 @@ impl Ppu {
                 0
             };
 
-        if scanline % 8 == 0 || dot % 8 == 0 {
+        if scanline.is_multiple_of(8) || dot.is_multiple_of(8) {
             //return PALETTE[0];
         }
 
         let background_palette_idx =
             self.get_vram_byte_at(0x3F00 + u16::from(background_pattern_final));
 
-        let square_sprites = (self.ppuctrl & 0x20) >> 5 == 0;
-
-        for sprite_idx in 0..64usize {
-            let sprite_y = self.oam[sprite_idx * 4] as u16 + 1; // "Sprite data is delayed by one scanline; you must subtract 1 from the sprite's Y coordinate before writing it here."
-            let pattern_idx = self.oam[sprite_idx * 4 + 1]; // As such, we must add 1 to the sprite's stored Y address to compensate for this.
-            let attributes = self.oam[sprite_idx * 4 + 2];
-            let sprite_x = self.oam[sprite_idx * 4 + 3] as u16;
-
-            let sprite_flipped_horizontally = attributes & (1 << 6) != 0;
-            let sprite_flipped_vertically = attributes & (1 << 7) != 0;
-            let pattern_high = attributes & 0b00000011;
-            let sprite_has_priority = (attributes & (1 << 5)) == 0;
-
-            if square_sprites
-                && dot >= sprite_x
-                && dot < (sprite_x + 8)
-                && scanline >= sprite_y
-                && scanline < (sprite_y + 8)
-            {
-                let sprite_height_offset = if !sprite_flipped_vertically {
-                    scanline - sprite_y
-                } else {
-                    7 - (scanline - sprite_y)
-                };
-
-                let sprite_pattern_start =
-                    ((u16::from(self.ppuctrl) << 9) & 0x1000) | (u16::from(pattern_idx) << 4);
-
-                let pattern_0 = self.get_vram_byte_at(sprite_pattern_start + sprite_height_offset);
-                let pattern_1 =
-                    self.get_vram_byte_at(sprite_pattern_start + sprite_height_offset + 8);
-
-                // We use this to calculate the offset into this "strip" of sprite data (the sprite
-                // pos along the x-axis). By default, this is 7 - (actual x - sprite start x).
-                // In the case where the sprite is horizontally flipped (bit 6 of attributes is
-                // active), we can simply use actual x - sprite start x.
-                let sprite_strip_offset = if !sprite_flipped_horizontally {
-                    7 - (dot - sprite_x)
-                } else {
-                    dot - sprite_x
-                };
-
-                // bit offset into pattern_0 and pattern_1 are overlaid
-                let pattern_low = ((pattern_0 >> sprite_strip_offset) & 0x1)
-                    | (((pattern_1 >> sprite_strip_offset) & 0x1) << 1);
-
-                let pattern_final = pattern_low | (pattern_high << 2);
-
-                // If the low bits 2 bits of the sprite idx (just the bits derived from the
-                // pattern), the sprite at this point is transparent.
-                if pattern_low == 0 {
+        // Sprites for this scanline were found and fetched at the end of the previous one. The
+        // first non-transparent sprite pixel wins.
+        if self.ppumask & (1 << 4) != 0 {
+            for slot in 0..usize::from(self.sprite_count) {
+                let sprite_x = u16::from(self.sprite_positions[slot]);
+                if dot < sprite_x || dot >= sprite_x + 8 {
                     continue;
                 }
 
-                // If we hit a sprite, set sprite 0 hit
-                if sprite_idx == 0 && pattern_low != 0 && background_pattern_final != 0 {
-                    self.ppustatus |= 1 << 6;
-                }
-
-                let palette_idx = self.get_vram_byte_at(0x3F10 + u16::from(pattern_final));
-
-                // If we found a sprite, but it doesn't have priority AND we found a
-                // non-transparent background pixel, the background pixel will be rendered instead
-                // of this sprite pixel.
-                if !sprite_has_priority && background_pattern_final != 0 {
-                    break;
-                }
-
-                return PALETTE[usize::from(palette_idx)];
-            } else if !square_sprites
-                && dot >= sprite_x
-                && dot < (sprite_x + 8)
-                && scanline >= sprite_y
-                && scanline < (sprite_y + 16)
-            {
-                let sprite_height_offset = if !sprite_flipped_vertically {
-                    scanline - sprite_y
-                } else {
-                    15 - (scanline - sprite_y)
-                };
-
-                let sprite_pattern_start = ((u16::from(pattern_idx) << 12) & 0x1000)
-                    | ((u16::from(pattern_idx) << 4) & 0x0FE0)
-                    | ((sprite_height_offset << 1) & 0x10);
-
-                let pattern_0 =
-                    self.get_vram_byte_at(sprite_pattern_start + (sprite_height_offset & 0x7));
-                let pattern_1 =
-                    self.get_vram_byte_at(sprite_pattern_start + (sprite_height_offset & 0x7) + 8);
-
-                // We use this to calculate the offset into this "strip" of sprite data (the sprite
-                // pos along the x-axis). By default, this is 7 - (actual x - sprite start x).
-                // In the case where the sprite is horizontally flipped (bit 6 of attributes is
-                // active), we can simply use actual x - sprite start x.
-                let sprite_strip_offset = if !sprite_flipped_horizontally {
-                    7 - (dot - sprite_x)
-                } else {
-                    dot - sprite_x
-                };
-
-                // bit offset into pattern_0 and pattern_1 are overlaid
-                let pattern_low = ((pattern_0 >> sprite_strip_offset) & 0x1)
-                    | (((pattern_1 >> sprite_strip_offset) & 0x1) << 1);
-
-                let pattern_final = pattern_low | (pattern_high << 2);
+                // Flipped sprites had their pattern bits reversed when fetched
+                let strip_offset = 7 - (dot - sprite_x);
+                let pattern_low = ((self.sprite_patterns_low[slot] >> strip_offset) & 0x1)
+                    | (((self.sprite_patterns_high[slot] >> strip_offset) & 0x1) << 1);
 
                 // If the low bits 2 bits of the sprite idx (just the bits derived from the
                 // pattern), the sprite at this point is transparent.
@@ -676,8 +736,11 @@ impl Ppu {
                     continue;
                 }
 
-                // If we hit a sprite, set sprite 0 hit
-                if sprite_idx == 0 && pattern_low != 0 && background_pattern_final != 0 {
+                let attributes = self.sprite_attributes[slot];
+                let pattern_final = pattern_low | ((attributes & 0b00000011) << 2);
+
+                // If we hit sprite 0, set sprite 0 hit
+                if slot == 0 && self.sprite_zero_on_line && background_pattern_final != 0 {
                     self.ppustatus |= 1 << 6;
                 }
 
@@ -686,78 +749,95 @@ impl Ppu {
                 // If we found a sprite, but it doesn't have priority AND we found a
                 // non-transparent background pixel, the background pixel will be rendered instead
                 // of this sprite pixel.
+                let sprite_has_priority = (attributes & (1 << 5)) == 0;
                 if !sprite_has_priority && background_pattern_final != 0 {
                     break;
                 }
 
-                return PALETTE[usize::from(palette_idx)];
+                return palette_idx & 0x3F;
             }
         }
 
-        PALETTE[usize::from(background_palette_idx)]
+        background_palette_idx & 0x3F
+    }
+
+    // The pre-render scanline is the last scanline of the frame: 261 on NTSC, 311 on PAL.
+    pub fn pre_render_scanline(&self) -> u16 {
+        match self.region {
+            rom::Region::Ntsc => 261,
+            rom::Region::Pal => 311,
+        }
+    }
+
+    // Whether the PPU is asserting NMI: it does while vblank is flagged and NMIs are enabled in
+    // PPUCTRL. The CPU triggers on the NMI being asserted, so enabling NMIs during vblank raises
+    // another one.
+    pub fn nmi_line(&self) -> bool {
+        self.ppustatus & 0x80 != 0 && self.ppuctrl & 0x80 != 0
     }
 
-    // pre-render scanline happens at 261
+    // pre-render scanline happens at 261 (NTSC) or 311 (PAL)
     // dot 0 is cycle 0
     pub fn step(&mut self) -> bool {
+        let pre_render_scanline = self.pre_render_scanline();
+
         // If we're at the part of the screen to be rendering:
-        if self.scanline <= 239 {
-            if self.cycle >= 2 && self.cycle <= 257 {
-                /* Psuedo-draw */
-
-                let dot = self.cycle - 2;
-                let curr_pixel_color = self.get_current_pixel(self.scanline, dot);
-
-                self.canvas.set_draw_color(curr_pixel_color);
-                self.canvas
-                    .fill_rect(Rect::new(
-                        dot as i32 * SCALE as i32,
-                        self.scanline as i32 * SCALE as i32,
-                        SCALE,
-                        SCALE,
-                    ))
-                    .unwrap();
-            }
+        if self.scanline <= 239 && self.cycle >= 2 && self.cycle <= 257 {
+            /* Psuedo-draw */
+
+            let dot = self.cycle - 2;
+            let curr_pixel = self.get_current_pixel(self.scanline, dot);
+            self.framebuffer[usize::from(self.scanline) * SCREEN_WIDTH + usize::from(dot)] =
+                curr_pixel;
         }
 
         // If rendering is enabled:
         if self.ppumask & 0x18 != 0 {
             // We only make memory accesses to PPU when rendering is active and on scanline 0-239
-            // or 261 (pre-render scanline)
-            if self.scanline <= 239 || self.scanline == 261 {
-                if (self.cycle >= 2 && self.cycle <= 257)
-                    || (self.cycle >= 322 && self.cycle <= 337)
-                {
-                    self.pattern_table_shift_low <<= 1;
-                    self.pattern_table_shift_high <<= 1;
-
-                    // If latch is set, make sure we set the bit that would be shifted in
-                    self.attribute_table_palette_shift_low <<= 1;
-                    if self.attribute_table_palette_latch_low {
-                        self.attribute_table_palette_shift_low |= 0x01;
-                    }
-
-                    self.attribute_table_palette_shift_high <<= 1;
-                    if self.attribute_table_palette_latch_high {
-                        self.attribute_table_palette_shift_high |= 0x01;
-                    }
-
-                    if self.cycle % 8 == 0 {
-                        self.coarse_x_increment();
-                        self.decode_pattern_table_high();
-                    } else if self.cycle % 8 == 1 {
-                        self.reload_shift_registers();
-                    } else if self.cycle % 8 == 3 {
-                        self.decode_nametable_byte();
-                    } else if self.cycle % 8 == 4 {
-                        self.decode_attribute_table_byte();
-                    } else if self.cycle % 8 == 6 {
-                        self.decode_pattern_table_low();
-                    }
+            // or the pre-render scanline
+            if (self.scanline <= 239 || self.scanline == pre_render_scanline)
+                && ((self.cycle >= 2 && self.cycle <= 257)
+                    || (self.cycle >= 322 && self.cycle <= 337))
+            {
+                self.pattern_table_shift_low <<= 1;
+                self.pattern_table_shift_high <<= 1;
+
+                // If latch is set, make sure we set the bit that would be shifted in
+                self.attribute_table_palette_shift_low <<= 1;
+                if self.attribute_table_palette_latch_low {
+                    self.attribute_table_palette_shift_low |= 0x01;
+                }
+
+                self.attribute_table_palette_shift_high <<= 1;
+                if self.attribute_table_palette_latch_high {
+                    self.attribute_table_palette_shift_high |= 0x01;
+                }
+
+                if self.cycle.is_multiple_of(8) {
+                    self.coarse_x_increment();
+                    self.decode_pattern_table_high();
+                } else if self.cycle % 8 == 1 {
+                    self.reload_shift_registers();
+                } else if self.cycle % 8 == 3 {
+                    self.decode_nametable_byte();
+                } else if self.cycle % 8 == 4 {
+                    self.decode_attribute_table_byte();
+                } else if self.cycle % 8 == 6 {
+                    self.decode_pattern_table_low();
+                }
+            }
+
+            if (self.scanline <= 239 || self.scanline == pre_render_scanline)
+                && self.cycle >= 257
+                && self.cycle <= 320
+            {
+                if self.cycle == 257 {
+                    self.evaluate_sprites();
                 }
+                self.fetch_sprites();
             }
 
-            if self.scanline <= 239 || self.scanline == 261 {
+            if self.scanline <= 239 || self.scanline == pre_render_scanline {
                 if self.cycle == 256 {
                     self.fine_y_increment();
                 } else if self.cycle == 257 {
@@ -770,7 +850,7 @@ impl Ppu {
                 }
             }
 
-            if self.scanline == 261 {
+            if self.scanline == pre_render_scanline {
                 if self.cycle >= 280 && self.cycle <= 304 {
                     // Reload vertical scroll bits
                     self.ppuaddr &= !0x7BE0;
@@ -779,8 +859,9 @@ impl Ppu {
                     // Update PPUCTRL nametable select to keep in sync
                     self.ppuctrl &= !0x2;
                     self.ppuctrl |= ((self.ppuaddr >> 10) & 0x3) as u8;
-                } else if self.cycle == 339 && !self.even_frame {
-                    // On odd frames, we skip right from (339, 261) to (0, 0) -> skip a cycle
+                } else if self.cycle == 339 && !self.even_frame && self.region == rom::Region::Ntsc
+                {
+                    // On odd NTSC frames, we skip right from (339, 261) to (0, 0) -> skip a cycle
                     self.cycle += 1;
                 }
             }
@@ -788,16 +869,25 @@ impl Ppu {
 
         if self.cycle == 1 {
             if self.scanline == 241 {
-                self.ppustatus |= 1 << 7; // set vblank at cycle 1 of scanline 241
-                self.nmi_waiting = (self.ppuctrl >> 7) & 0x1 != 0; //Nmi only occurs on vblank if ppuctrl bit 7 is set
-            } else if self.scanline == 261 {
-                self.ppustatus &= !(1 << 6); // clear sprite 0 hit at cycle 1 of scaline 261 (pre-render line)
-                self.ppustatus &= !(1 << 7); // clear vblank at cycle 1 of scanline 261 (pre-render line)
+                if !self.suppress_vblank {
+                    self.ppustatus |= 1 << 7; // set vblank at cycle 1 of scanline 241
+                }
+                self.suppress_vblank = false;
+            } else if self.scanline == pre_render_scanline {
+                self.ppustatus &= !(1 << 6); // clear sprite 0 hit at cycle 1 of the pre-render line
+                self.ppustatus &= !(1 << 5); // clear sprite overflow at cycle 1 of the pre-render line
+                self.ppustatus &= !(1 << 7); // clear vblank at cycle 1 of the pre-render line
             }
         }
 
+        // With rendering off, nothing is evaluated or fetched for the next scanline
+        if self.ppumask & 0x18 == 0 && self.cycle == 257 {
+            self.sprite_count = 0;
+        }
+
         // OAMADDR gets set to 0 during ticks 257-320 of pre-render and visible scanlines
-        if (self.scanline == 261 || self.scanline < 240) && (self.cycle >= 257 && self.cycle <= 320)
+        if (self.scanline == pre_render_scanline || self.scanline < 240)
+            && (self.cycle >= 257 && self.cycle <= 320)
         {
             self.oamaddr = 0;
         }
@@ -808,10 +898,9 @@ impl Ppu {
         if self.cycle > 340 {
             self.cycle = 0;
             self.scanline += 1;
-            if self.scanline > 261 {
+            if self.scanline > pre_render_scanline {
                 self.scanline = 0;
                 self.even_frame = !self.even_frame;
-                self.canvas.present();
                 return true;
             }
         }
@@ -819,6 +908,115 @@ impl Ppu {
         false
     }
 
+    fn sprite_height(&self) -> u16 {
+        if self.ppuctrl & 0x20 == 0 {
+            8
+        } else {
+            16
+        }
+    }
+
+    // Find the (up to 8) sprites on the next scanline, in OAM order. The PPU does this over dots
+    // 65-256, but nothing can observe it before the fetches at 257-320, so it's done all at once.
+    fn evaluate_sprites(&mut self) {
+        self.sprite_count = 0;
+        self.sprite_zero_on_line = false;
+
+        // Nothing is drawn on the first visible scanline, so the pre-render line finds nothing
+        if self.scanline == self.pre_render_scanline() {
+            return;
+        }
+
+        let sprite_height = self.sprite_height();
+        for sprite_idx in 0..64usize {
+            // Sprite data is delayed by one scanline, so the sprite whose Y is this scanline
+            // shows up starting on the next one.
+            let sprite_y = u16::from(self.oam[sprite_idx * 4]);
+            if self.scanline < sprite_y || self.scanline >= sprite_y + sprite_height {
+                continue;
+            }
+
+            if self.sprite_count == 8 {
+                self.ppustatus |= 1 << 5; // sprite overflow
+                break;
+            }
+
+            let slot = usize::from(self.sprite_count);
+            self.sprite_indices[slot] = sprite_idx as u8;
+            self.sprite_zero_on_line |= sprite_idx == 0;
+            self.sprite_count += 1;
+        }
+    }
+
+    // One step of the sprite fetches over dots 257-320: 8 dots per sprite, with two garbage
+    // nametable fetches followed by the low and high pattern bytes. Unused slots fetch tile 0xFF,
+    // which still matters to mappers watching the address bus.
+    fn fetch_sprites(&mut self) {
+        let slot = usize::from((self.cycle - 257) / 8);
+
+        match (self.cycle - 257) % 8 {
+            0 | 2 => {
+                self.get_vram_byte_at(0x2000 | (self.ppuaddr & 0x0FFF));
+            }
+            4 | 6 => {
+                let high_plane = (self.cycle - 257) % 8 == 6;
+                let (pattern_addr, flip_horizontally) = self.sprite_pattern_addr(slot);
+
+                let mut pattern =
+                    self.get_vram_byte_at(pattern_addr + if high_plane { 8 } else { 0 });
+                if slot >= usize::from(self.sprite_count) {
+                    pattern = 0;
+                } else if flip_horizontally {
+                    pattern = pattern.reverse_bits();
+                }
+
+                if high_plane {
+                    self.sprite_patterns_high[slot] = pattern;
+                } else {
+                    self.sprite_patterns_low[slot] = pattern;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Address of the low pattern byte of the row of the sprite in the given slot that's on the
+    // next scanline, and whether the sprite is flipped horizontally. Also latches the sprite's
+    // attributes and X position.
+    fn sprite_pattern_addr(&mut self, slot: usize) -> (u16, bool) {
+        let sprite_height = self.sprite_height();
+
+        let (pattern_idx, attributes, row) = if slot < usize::from(self.sprite_count) {
+            let sprite_idx = usize::from(self.sprite_indices[slot]);
+            let sprite_y = u16::from(self.oam[sprite_idx * 4]);
+            let attributes = self.oam[sprite_idx * 4 + 2];
+
+            self.sprite_attributes[slot] = attributes;
+            self.sprite_positions[slot] = self.oam[sprite_idx * 4 + 3];
+
+            let mut row = self.scanline - sprite_y;
+            if attributes & (1 << 7) != 0 {
+                row = sprite_height - 1 - row; // flipped vertically
+            }
+
+            (self.oam[sprite_idx * 4 + 1], attributes, row)
+        } else {
+            (0xFF, 0, 0)
+        };
+
+        let addr = if sprite_height == 8 {
+            ((u16::from(self.ppuctrl) << 9) & 0x1000) | (u16::from(pattern_idx) << 4) | row
+        } else {
+            // 8x16 sprites pick their pattern table with bit 0 of the tile index
+            ((u16::from(pattern_idx) << 12) & 0x1000)
+                | ((u16::from(pattern_idx) << 4) & 0x0FE0)
+                | ((row << 1) & 0x10)
+                | (row & 0x7)
+        };
+
+        (addr, attributes & (1 << 6) != 0)
+    }
+
     fn reload_shift_registers(&mut self) {
         // Clear the low 8 bits of the shift registers.
         self.pattern_table_shift_low &= 0xFF00;
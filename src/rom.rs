@@ -1,75 +1,244 @@
-use std::convert::TryFrom;
+pub mod database;
+
+use crate::checksum;
+use crate::error::EmuError;
+use crate::patch;
+use crate::rom::database::RomDatabase;
+
+use std::convert::TryInto;
 use std::fs::File;
-use std::io::{Error, ErrorKind, Read};
+use std::io::Read;
+use std::path::Path;
 
 #[derive(Debug)]
 pub struct Rom {
     pub prg_rom: Vec<u8>,
     pub chr_rom: Vec<u8>,
-    pub mapper_number: u8,
+    pub nes2: bool, // whether the header is in the NES 2.0 format
+    pub mapper_number: u16,
+    pub submapper_number: u8, // board variant of the mapper; only NES 2.0 headers have one
     pub mirroring: MirroringType,
     pub battery_backed_ram: bool,
-    pub trainer: bool,
+    pub trainer: Option<Vec<u8>>, // 512 bytes that copiers loaded into $7000-$71FF
+    pub prg_ram_size: usize,      // bytes of PRG RAM that isn't battery-backed
+    pub prg_nvram_size: usize,    // bytes of battery-backed PRG RAM
+    pub chr_ram_size: usize,      // bytes of CHR RAM that isn't battery-backed
+    pub chr_nvram_size: usize,    // bytes of battery-backed CHR RAM
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    pub misc_rom_count: u8,   // number of extra ROM areas after the CHR ROM
+    pub expansion_device: u8, // default expansion port device, as numbered by NES 2.0
+    pub crc32: u32,           // of the PRG ROM followed by the CHR ROM, for identifying the game
+    pub sha1: [u8; 20],       // same, as SHA-1
+    pub corrections: Vec<String>, // header fields a ROM database corrected, as "field: old -> new"
+    pub warnings: Vec<String>, // problems with the file that loading worked around
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MirroringType {
     Horizontal,
     Vertical,
     FourScreen,
+    SingleScreenLower, // every nametable is the first 1KB of nametable RAM
+    SingleScreenUpper, // every nametable is the second 1KB of nametable RAM
+}
+
+// Which console's CPU/PPU timing the game was made for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultiRegion, // works with either
+    Dendy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsoleType {
+    Nes,                                // also the Famicom and Dendy
+    VsSystem { ppu: u8, hardware: u8 }, // Vs. System PPU and hardware types, as numbered by NES 2.0
+    Playchoice10,
+    Extended(u8), // one of the other console types NES 2.0 numbers
+}
+
+// Console region, which determines the master clock rate, the clock dividers of the CPU and PPU,
+// and the number of scanlines per frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+}
+
+impl Region {
+    pub fn master_clock_hz(self) -> u64 {
+        match self {
+            Region::Ntsc => 236_250_000 / 11, // 21.477272 MHz
+            Region::Pal => 26_601_712,        // 26.601712 MHz
+        }
+    }
+
+    // Number of master clock ticks per CPU cycle
+    pub fn cpu_divider(self) -> u64 {
+        match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+        }
+    }
+
+    pub fn frames_per_second(self) -> f64 {
+        match self {
+            Region::Ntsc => 60.0988,
+            Region::Pal => 50.007,
+        }
+    }
+
+    // Number of master clock ticks per PPU dot
+    pub fn ppu_divider(self) -> u64 {
+        match self {
+            Region::Ntsc => 4,
+            Region::Pal => 5,
+        }
+    }
 }
 
 impl Rom {
-    pub fn new(filename: &str) -> Result<Rom, std::io::Error> {
-        let mut f = File::open(filename)?;
+    pub fn new(filename: &str) -> Result<Rom, EmuError> {
+        Self::from_reader(File::open(filename)?)
+    }
 
-        // Assume header starts at "byte 0"
-        let mut header: [u8; 16] = [0; 16];
+    // Load a ROM file with an IPS, UPS or BPS patch applied to it.
+    pub fn new_patched(filename: &str, patch_filename: &str) -> Result<Rom, EmuError> {
+        let file = std::fs::read(filename)?;
+        let patch = std::fs::read(patch_filename)?;
+        Self::from_bytes(&patch::apply(&file, &patch)?)
+    }
+
+    // Find a patch next to the ROM file with the same name, the way patching emulators look for
+    // them: game.nes is patched by game.ips, game.ups or game.bps.
+    pub fn find_patch(filename: &str) -> Option<String> {
+        ["ips", "ups", "bps"]
+            .iter()
+            .map(|extension| Path::new(filename).with_extension(extension))
+            .find(|path| path.is_file())
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
+    pub fn from_reader(mut reader: impl Read) -> Result<Rom, EmuError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Self::from_bytes(&data)
+    }
+
+    // Parse an iNES or NES 2.0 file that's already in memory, correcting its header from the
+    // built-in ROM database.
+    pub fn from_bytes(file: &[u8]) -> Result<Rom, EmuError> {
+        Self::from_bytes_with_database(file, Some(&RomDatabase::builtin()))
+    }
 
-        f.read_exact(&mut header)?;
+    // Parse an iNES or NES 2.0 file that's already in memory, correcting its header from the given
+    // ROM database, or taking the header as it is if there's none.
+    pub fn from_bytes_with_database(
+        file: &[u8],
+        database: Option<&RomDatabase>,
+    ) -> Result<Rom, EmuError> {
+        // Assume header starts at "byte 0"
+        if file.len() < 16 {
+            return Err(EmuError::InvalidRom(
+                "File is too short to have a header".to_string(),
+            ));
+        }
+        let mut header: [u8; 16] = file[0..16].try_into().unwrap();
 
         if header[0..4] != *b"NES\x1a" {
-            return Err(Error::new(ErrorKind::InvalidData, "Rom had invalid header"));
+            return Err(EmuError::InvalidRom("Rom had invalid header".to_string()));
+        }
+
+        let mut data = file[16..].to_vec();
+
+        // NES 2.0 headers are marked by bits 2-3 of byte 7 being 0b10, and use bytes 8-15 for
+        // extra information that iNES headers leave as 0
+        let nes2 = header[7] & 0b00001100 == 0b00001000;
+
+        let mut warnings = Vec::new();
+
+        // Old dumping tools wrote junk like "DiskDude!" over bytes 7-15 of iNES headers. When
+        // it's there, none of those bytes can be trusted, so treat them as 0 like other emulators.
+        if !nes2 && (header[7] & 0b00001100 != 0 || header[12..16] != [0; 4]) {
+            warnings.push("ignoring garbage in bytes 7-15 of the ROM header".to_string());
+            header[7..16].fill(0);
         }
 
-        let prg_rom_banks = header[4];
-        let chr_rom_banks = header[5];
         let rom_ctrl_byte_1 = header[6];
         let rom_ctrl_byte_2 = header[7];
-        let _prg_ram_banks = std::cmp::max(header[8], 1); //assume 1 bank exists even when 0
 
-        if header[9..16] != [0; 7] {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Bytes 9-15 of header are not all 0",
-            ));
-        }
+        let battery_backed_ram = (rom_ctrl_byte_1 & (1 << 1)) != 0;
 
-        if (rom_ctrl_byte_2 & (0b00001111)) != 0 {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Bits 0-3 of Rom Control Byte 2 are not all 0",
-            ));
-        }
+        // The trainer, if there is one, comes before the PRG ROM
+        let trainer = if (rom_ctrl_byte_1 & (1 << 2)) != 0 {
+            if data.len() < 0x200 {
+                return Err(EmuError::InvalidRom(
+                    "File is too short to have the trainer the header says it has".to_string(),
+                ));
+            }
+            Some(data.drain(..0x200).collect())
+        } else {
+            None
+        };
+        let mut mapper_number =
+            u16::from((rom_ctrl_byte_2 & 0b11110000) | ((rom_ctrl_byte_1 & 0b11110000) >> 4));
 
-        let prg_bytes = u32::try_from(prg_rom_banks).unwrap() * 0x4000;
-        let chr_bytes = u32::try_from(chr_rom_banks).unwrap() * 0x2000;
+        let (prg_bytes, chr_bytes) = if nes2 {
+            mapper_number |= u16::from(header[8] & 0x0F) << 8;
+            (
+                Self::nes2_rom_size(header[4], header[9] & 0x0F, 0x4000)?,
+                Self::nes2_rom_size(header[5], header[9] >> 4, 0x2000)?,
+            )
+        } else {
+            (
+                usize::from(header[4]) * 0x4000,
+                usize::from(header[5]) * 0x2000,
+            )
+        };
 
-        let mut prg_rom = vec![0u8; usize::try_from(prg_bytes).unwrap()];
-        f.read_exact(&mut prg_rom)?;
+        // Some dumps are missing their last bank, so pad it out rather than refusing to load. Any
+        // data after the CHR ROM is ignored, unless the header says it's a miscellaneous ROM.
+        let rom_bytes = prg_bytes
+            .checked_add(chr_bytes)
+            .ok_or_else(|| EmuError::InvalidRom("ROM size in header is too large".to_string()))?;
+        let misc_rom_count = if nes2 { header[14] & 0x3 } else { 0 };
+        if data.len() < rom_bytes {
+            let last_bank_size = if chr_bytes > 0 { 0x2000 } else { 0x4000 };
+            if rom_bytes - data.len() > last_bank_size {
+                return Err(EmuError::InvalidRom(format!(
+                    "File is {} bytes shorter than the sizes in the header",
+                    rom_bytes - data.len()
+                )));
+            }
+            warnings.push(format!(
+                "ROM file is {} bytes short, padding out its last bank",
+                rom_bytes - data.len()
+            ));
+            data.resize(rom_bytes, 0);
+        } else if data.len() > rom_bytes && misc_rom_count == 0 {
+            warnings.push(format!(
+                "ignoring {} bytes of data after the end of the ROM",
+                data.len() - rom_bytes
+            ));
+        }
 
-        let mut chr_rom = vec![0u8; usize::try_from(chr_bytes).unwrap()];
-        f.read_exact(&mut chr_rom)?;
+        data.truncate(rom_bytes);
+        let crc32 = checksum::crc32(&data);
+        let sha1 = checksum::sha1(&data);
 
-        // Ensure that we've read all the necessary data from the file correctly, to the end of the
-        // ROM file.
-        let file_metadata = f.metadata()?;
-        assert!(file_metadata.len() == u64::from(prg_bytes + chr_bytes + 0x10));
+        let chr_rom = data[prg_bytes..].to_vec();
+        data.truncate(prg_bytes);
+        let prg_rom = data;
 
-        let res = Rom {
+        let mut res = Rom {
             prg_rom,
             chr_rom,
-            mapper_number: (rom_ctrl_byte_2 & 0b11110000) | ((rom_ctrl_byte_1 & 0b11110000) >> 4),
+            nes2,
+            mapper_number,
+            submapper_number: 0,
             mirroring: if (rom_ctrl_byte_1 & (1 << 3)) != 0 {
                 MirroringType::FourScreen
             } else if (rom_ctrl_byte_1 & (1 << 0)) == 0 {
@@ -77,11 +246,199 @@ impl Rom {
             } else {
                 MirroringType::Vertical
             },
-            battery_backed_ram: (rom_ctrl_byte_1 & (1 << 1)) != 0,
-            trainer: (rom_ctrl_byte_1 & (1 << 2)) != 0,
+            battery_backed_ram,
+            trainer,
+            prg_ram_size: 0,
+            prg_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+            timing: Timing::Ntsc,
+            console_type: ConsoleType::Nes,
+            misc_rom_count,
+            expansion_device: 0,
+            crc32,
+            sha1,
+            corrections: Vec::new(),
+            warnings,
         };
-        println!("Mirroring: {:?}", res.mirroring);
-        println!("Mapper Number: {}", res.mapper_number);
+
+        if nes2 {
+            res.submapper_number = header[8] >> 4;
+            res.prg_ram_size = Self::nes2_ram_size(header[10] & 0x0F);
+            res.prg_nvram_size = Self::nes2_ram_size(header[10] >> 4);
+            res.chr_ram_size = Self::nes2_ram_size(header[11] & 0x0F);
+            res.chr_nvram_size = Self::nes2_ram_size(header[11] >> 4);
+            res.timing = match header[12] & 0x3 {
+                0 => Timing::Ntsc,
+                1 => Timing::Pal,
+                2 => Timing::MultiRegion,
+                _ => Timing::Dendy,
+            };
+            res.console_type = match rom_ctrl_byte_2 & 0x3 {
+                0 => ConsoleType::Nes,
+                1 => ConsoleType::VsSystem {
+                    ppu: header[13] & 0x0F,
+                    hardware: header[13] >> 4,
+                },
+                2 => ConsoleType::Playchoice10,
+                _ => ConsoleType::Extended(header[13] & 0x0F),
+            };
+            res.expansion_device = header[15] & 0x3F;
+        } else {
+            // iNES headers give PRG RAM in 8KB units, where 0 also means 8KB, and games without
+            // CHR ROM have 8KB of CHR RAM. Byte 9 has a PAL flag, and byte 7 flags Vs. System and
+            // PlayChoice-10 games.
+            let prg_ram_size = usize::from(std::cmp::max(header[8], 1)) * 0x2000;
+            res.timing = if header[9] & 1 != 0 {
+                Timing::Pal
+            } else {
+                Timing::Ntsc
+            };
+            res.console_type = match rom_ctrl_byte_2 & 0x3 {
+                0 => ConsoleType::Nes,
+                1 => ConsoleType::VsSystem {
+                    ppu: 0,
+                    hardware: 0,
+                },
+                _ => ConsoleType::Playchoice10,
+            };
+            if battery_backed_ram {
+                res.prg_nvram_size = prg_ram_size;
+            } else {
+                res.prg_ram_size = prg_ram_size;
+            }
+            if res.chr_rom.is_empty() {
+                res.chr_ram_size = 0x2000;
+            }
+        }
+
+        // Plenty of dumps in circulation have wrong headers, so correct the ones we know about
+        if let Some(database) = database {
+            database.apply(&mut res);
+        }
+
         Ok(res)
     }
+
+    // The region to emulate for the ROM's timing. Dendy timing isn't emulated, so Dendy games run
+    // as NTSC.
+    pub fn region(&self) -> Region {
+        match self.timing {
+            Timing::Pal => Region::Pal,
+            Timing::Ntsc | Timing::MultiRegion | Timing::Dendy => Region::Ntsc,
+        }
+    }
+
+    // Size of the PRG or CHR ROM in a NES 2.0 header. Normally it's a 12-bit count of units, but
+    // if the top 4 bits are all set, the low byte is instead an exponent and multiplier:
+    // 2^E * (MM * 2 + 1) bytes, for a low byte of 0bEEEEEEMM.
+    fn nes2_rom_size(lsb: u8, msb: u8, unit: usize) -> Result<usize, EmuError> {
+        if msb != 0x0F {
+            return Ok(((usize::from(msb) << 8) | usize::from(lsb)) * unit);
+        }
+
+        let exponent = u32::from(lsb >> 2);
+        let multiplier = usize::from(lsb & 0x3) * 2 + 1;
+        2usize
+            .checked_pow(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+            .ok_or_else(|| EmuError::InvalidRom("ROM size in header is too large".to_string()))
+    }
+
+    // Size of a RAM area in a NES 2.0 header, given as a shift count: 64 << n bytes, or none for 0.
+    fn nes2_ram_size(shift: u8) -> usize {
+        if shift == 0 {
+            0
+        } else {
+            64 << shift
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(header: [u8; 16], rom_bytes: usize) -> Result<Rom, EmuError> {
+        let mut file = header.to_vec();
+        file.resize(16 + rom_bytes, 0);
+        Rom::from_bytes_with_database(&file, None)
+    }
+
+    #[test]
+    fn ines_defaults() {
+        let mut header = [0; 16];
+        header[0..4].copy_from_slice(b"NES\x1a");
+        header[4] = 2;
+        header[6] = 0x13; // mapper 1, battery, vertical mirroring
+        header[9] = 1;
+        let rom = parse(header, 0x8000).unwrap();
+
+        assert!(!rom.nes2);
+        assert_eq!(rom.mapper_number, 1);
+        assert_eq!(rom.prg_rom.len(), 0x8000);
+        assert!(rom.chr_rom.is_empty());
+        assert_eq!(rom.mirroring, MirroringType::Vertical);
+        assert_eq!(rom.prg_ram_size, 0);
+        assert_eq!(rom.prg_nvram_size, 0x2000);
+        assert_eq!(rom.chr_ram_size, 0x2000);
+        assert_eq!(rom.timing, Timing::Pal);
+        assert!(rom.warnings.is_empty());
+    }
+
+    #[test]
+    fn nes2_header() {
+        let mut header = [0; 16];
+        header[0..4].copy_from_slice(b"NES\x1a");
+        header[4] = (12 << 2) | 1; // 2^12 * 3 bytes of PRG ROM
+        header[6] = 0x42; // mapper 4, battery
+        header[7] = 0x08;
+        header[8] = 0x31; // submapper 3, mapper 0x104
+        header[9] = 0x0F; // PRG ROM size in exponent form
+        header[10] = 0x70; // 8KB PRG NVRAM
+        header[11] = 0x07; // 8KB CHR RAM
+        header[12] = 1;
+        let rom = parse(header, 12288).unwrap();
+
+        assert!(rom.nes2);
+        assert_eq!(rom.mapper_number, 0x104);
+        assert_eq!(rom.submapper_number, 3);
+        assert_eq!(rom.prg_rom.len(), 12288);
+        assert!(rom.chr_rom.is_empty());
+        assert!(rom.battery_backed_ram);
+        assert_eq!(rom.prg_ram_size, 0);
+        assert_eq!(rom.prg_nvram_size, 0x2000);
+        assert_eq!(rom.chr_ram_size, 0x2000);
+        assert_eq!(rom.chr_nvram_size, 0);
+        assert_eq!(rom.timing, Timing::Pal);
+        assert_eq!(rom.region(), Region::Pal);
+    }
+
+    #[test]
+    fn rom_size_too_large() {
+        let mut header = [0; 16];
+        header[0..4].copy_from_slice(b"NES\x1a");
+        header[7] = 0x08;
+        header[9] = 0xFF;
+        // Each size fits on its own, but not added together
+        header[4] = ((usize::BITS - 1) << 2) as u8;
+        header[5] = ((usize::BITS - 1) << 2) as u8;
+        assert!(matches!(parse(header, 0), Err(EmuError::InvalidRom(_))));
+
+        header[4] = 63 << 2 | 3;
+        assert!(matches!(parse(header, 0), Err(EmuError::InvalidRom(_))));
+    }
+
+    #[test]
+    fn garbage_header() {
+        let mut header = [0; 16];
+        header[0..4].copy_from_slice(b"NES\x1a");
+        header[4] = 1;
+        header[5] = 1;
+        header[7..16].copy_from_slice(b"DiskDude!");
+        let rom = parse(header, 0x6000).unwrap();
+
+        assert_eq!(rom.mapper_number, 0);
+        assert_eq!(rom.warnings.len(), 1);
+    }
 }
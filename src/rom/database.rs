@@ -0,0 +1,243 @@
+use crate::error::EmuError;
+use crate::rom::{MirroringType, Rom, Timing};
+
+use std::collections::HashMap;
+
+// Corrections for cartridges whose dumps commonly have wrong headers. See database.txt for the
+// format, which override files share.
+const BUILTIN: &str = include_str!("database.txt");
+
+// Header fields to override for one cartridge. Fields that are None are left as the header has
+// them.
+#[derive(Clone, Debug, Default)]
+pub struct Entry {
+    pub mapper_number: Option<u16>,
+    pub submapper_number: Option<u8>,
+    pub mirroring: Option<MirroringType>,
+    pub battery_backed_ram: Option<bool>,
+    pub timing: Option<Timing>,
+    pub prg_ram_size: Option<usize>,
+    pub prg_nvram_size: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct RomDatabase {
+    pub entries: HashMap<u32, Entry>, // keyed by CRC32 of PRG ROM + CHR ROM
+}
+
+impl RomDatabase {
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("built-in ROM database is malformed")
+    }
+
+    pub fn load(filename: &str) -> Result<Self, EmuError> {
+        Self::parse(&std::fs::read_to_string(filename)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, EmuError> {
+        let mut res = Self::default();
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            let mut fields = line.split_whitespace();
+
+            let crc = match fields.next() {
+                Some(crc) => crc,
+                None => continue,
+            };
+            let error = |reason: String| {
+                EmuError::InvalidRomDatabase(format!("line {}: {}", line_number + 1, reason))
+            };
+
+            let crc = u32::from_str_radix(crc, 16)
+                .map_err(|_| error(format!("invalid CRC32 '{}'", crc)))?;
+
+            let mut entry = Entry::default();
+            for field in fields {
+                let (key, val) = match field.find('=') {
+                    Some(pos) => (&field[..pos], &field[pos + 1..]),
+                    None => return Err(error(format!("expected key=value, got '{}'", field))),
+                };
+                let invalid = || error(format!("invalid value for {}: '{}'", key, val));
+
+                match key {
+                    "mapper" => entry.mapper_number = Some(val.parse().map_err(|_| invalid())?),
+                    "submapper" => {
+                        entry.submapper_number = Some(val.parse().map_err(|_| invalid())?)
+                    }
+                    "mirroring" => {
+                        entry.mirroring = Some(match val {
+                            "horizontal" => MirroringType::Horizontal,
+                            "vertical" => MirroringType::Vertical,
+                            "four-screen" => MirroringType::FourScreen,
+                            _ => return Err(invalid()),
+                        })
+                    }
+                    "battery" => {
+                        entry.battery_backed_ram = Some(match val {
+                            "yes" => true,
+                            "no" => false,
+                            _ => return Err(invalid()),
+                        })
+                    }
+                    "timing" => {
+                        entry.timing = Some(match val {
+                            "ntsc" => Timing::Ntsc,
+                            "pal" => Timing::Pal,
+                            "multi-region" => Timing::MultiRegion,
+                            "dendy" => Timing::Dendy,
+                            _ => return Err(invalid()),
+                        })
+                    }
+                    "prg_ram" => entry.prg_ram_size = Some(Self::size(val).ok_or_else(invalid)?),
+                    "prg_nvram" => {
+                        entry.prg_nvram_size = Some(Self::size(val).ok_or_else(invalid)?)
+                    }
+                    _ => return Err(error(format!("unknown key '{}'", key))),
+                }
+            }
+
+            res.entries.insert(crc, entry);
+        }
+
+        Ok(res)
+    }
+
+    // A size in bytes, or in KB with a k suffix, up to the largest a NES 2.0 header can give
+    fn size(val: &str) -> Option<usize> {
+        let size = if let Some(kb) = val.strip_suffix('k') {
+            kb.parse::<usize>().ok()?.checked_mul(1024)?
+        } else {
+            val.parse().ok()?
+        };
+        (size <= 64 << 15).then_some(size)
+    }
+
+    // Correct the ROM's header fields if the database knows the cartridge, noting each change in
+    // rom.corrections. Returns whether anything changed.
+    pub fn apply(&self, rom: &mut Rom) -> bool {
+        let entry = match self.entries.get(&rom.crc32) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        let corrections = &mut rom.corrections;
+        let count = corrections.len();
+        Self::correct(
+            "mapper",
+            &mut rom.mapper_number,
+            entry.mapper_number,
+            corrections,
+        );
+        Self::correct(
+            "submapper",
+            &mut rom.submapper_number,
+            entry.submapper_number,
+            corrections,
+        );
+        Self::correct(
+            "mirroring",
+            &mut rom.mirroring,
+            entry.mirroring,
+            corrections,
+        );
+        Self::correct(
+            "battery",
+            &mut rom.battery_backed_ram,
+            entry.battery_backed_ram,
+            corrections,
+        );
+        Self::correct("timing", &mut rom.timing, entry.timing, corrections);
+        Self::correct(
+            "prg_ram",
+            &mut rom.prg_ram_size,
+            entry.prg_ram_size,
+            corrections,
+        );
+        Self::correct(
+            "prg_nvram",
+            &mut rom.prg_nvram_size,
+            entry.prg_nvram_size,
+            corrections,
+        );
+        corrections.len() > count
+    }
+
+    fn correct<T: PartialEq + std::fmt::Debug>(
+        name: &str,
+        field: &mut T,
+        val: Option<T>,
+        corrections: &mut Vec<String>,
+    ) {
+        if let Some(val) = val {
+            if *field != val {
+                corrections.push(format!("{}: {:?} -> {:?}", name, field, val));
+                *field = val;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An iNES file for an NROM board with horizontal mirroring and no battery
+    fn nrom_file() -> Vec<u8> {
+        let mut file = b"NES\x1a\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        file.extend((0..0x6000).map(|i| i as u8));
+        file
+    }
+
+    #[test]
+    fn builtin_parses() {
+        assert!(!RomDatabase::builtin().entries.is_empty());
+    }
+
+    #[test]
+    fn overrides_wrong_header() {
+        let mut rom = Rom::from_bytes_with_database(&nrom_file(), None).unwrap();
+        assert_eq!(rom.mapper_number, 0);
+
+        let database = RomDatabase::parse(&format!(
+            "{:08X} mapper=4 submapper=1 mirroring=vertical battery=yes prg_ram=0 prg_nvram=8k \
+             # comment",
+            rom.crc32
+        ))
+        .unwrap();
+        assert!(database.apply(&mut rom));
+
+        assert_eq!(rom.mapper_number, 4);
+        assert_eq!(rom.submapper_number, 1);
+        assert_eq!(rom.mirroring, MirroringType::Vertical);
+        assert!(rom.battery_backed_ram);
+        assert_eq!(rom.prg_ram_size, 0);
+        assert_eq!(rom.prg_nvram_size, 0x2000);
+
+        assert_eq!(rom.corrections.len(), 6);
+        assert_eq!(rom.corrections[0], "mapper: 0 -> 4");
+
+        // Applying it again changes nothing
+        assert!(!database.apply(&mut rom));
+    }
+
+    #[test]
+    fn ignores_other_roms() {
+        let mut rom = Rom::from_bytes_with_database(&nrom_file(), None).unwrap();
+        let database = RomDatabase::parse(&format!("{:08X} mapper=4", !rom.crc32)).unwrap();
+        assert!(!database.apply(&mut rom));
+        assert_eq!(rom.mapper_number, 0);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(RomDatabase::parse("XYZ mapper=1").is_err());
+        assert!(RomDatabase::parse("12345678 mapper").is_err());
+        assert!(RomDatabase::parse("12345678 colour=red").is_err());
+        assert!(RomDatabase::parse("12345678 mirroring=diagonal").is_err());
+        assert!(RomDatabase::parse("12345678 prg_ram=8m").is_err());
+        assert!(RomDatabase::parse("12345678 prg_ram=2048k").is_ok());
+        assert!(RomDatabase::parse("12345678 prg_ram=2049k").is_err());
+        assert!(RomDatabase::parse("12345678 prg_nvram=18446744073709551615").is_err());
+    }
+}
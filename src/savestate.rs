@@ -0,0 +1,128 @@
+use crate::cpu::Cpu;
+
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind};
+
+// Savestates are a magic number and format version followed by the state of every component,
+// written in a fixed order with all multi-byte values little-endian.
+const MAGIC: &[u8; 4] = b"NESS";
+const VERSION: u32 = 13;
+
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self { data: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, val: u8) {
+        self.data.push(val);
+    }
+
+    pub fn write_bool(&mut self, val: bool) {
+        self.data.push(val as u8);
+    }
+
+    pub fn write_u16(&mut self, val: u16) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, val: u32) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, val: u64) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, val: &[u8]) {
+        self.data.extend_from_slice(val);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], std::io::Error> {
+        if self.data.len() < len {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Savestate ended unexpectedly",
+            ));
+        }
+
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, std::io::Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, std::io::Error> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, std::io::Error> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, std::io::Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, std::io::Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_bytes(&mut self, out: &mut [u8]) -> Result<(), std::io::Error> {
+        out.copy_from_slice(self.take(out.len())?);
+        Ok(())
+    }
+}
+
+pub fn save(cpu: &Cpu) -> Vec<u8> {
+    let mut w = StateWriter::new();
+    w.write_bytes(MAGIC);
+    w.write_u32(VERSION);
+    cpu.save_state(&mut w);
+
+    w.into_bytes()
+}
+
+pub fn load(cpu: &mut Cpu, data: &[u8]) -> Result<(), std::io::Error> {
+    let mut r = StateReader::new(data);
+
+    let mut magic = [0u8; 4];
+    r.read_bytes(&mut magic)?;
+    if magic != *MAGIC {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "File is not a savestate",
+        ));
+    }
+
+    let version = r.read_u32()?;
+    if version != VERSION {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Unsupported savestate version {}", version),
+        ));
+    }
+
+    cpu.load_state(&mut r)
+}